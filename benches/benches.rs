@@ -1,8 +1,13 @@
 //! Benchmark for various NearestNeighbors implementations.
 
+use acap::ball::{BallTree, FlatBallTree};
+use acap::cover::CoverTree;
 use acap::euclid::Euclidean;
 use acap::exhaustive::ExhaustiveSearch;
+use acap::hnsw::Hnsw;
+use acap::ivf::Ivf;
 use acap::kd::{FlatKdTree, KdTree};
+use acap::rp::RpForest;
 use acap::vp::{FlatVpTree, VpTree};
 use acap::NearestNeighbors;
 
@@ -51,6 +56,12 @@ fn bench_creation(c: &mut Criterion) {
     bench!(FlatVpTree);
     bench!(KdTree);
     bench!(FlatKdTree);
+    bench!(BallTree);
+    bench!(FlatBallTree);
+    bench!(CoverTree);
+    bench!(Hnsw);
+    bench!(Ivf);
+    bench!(RpForest);
 
     group.finish();
 }
@@ -103,6 +114,12 @@ fn bench_nearest_neighbors(c: &mut Criterion) {
     bench!(FlatVpTree);
     bench!(KdTree);
     bench!(FlatKdTree);
+    bench!(BallTree);
+    bench!(FlatBallTree);
+    bench!(CoverTree);
+    bench!(Hnsw);
+    bench!(Ivf);
+    bench!(RpForest);
 }
 
 criterion_group!(benches, bench_creation, bench_nearest_neighbors);
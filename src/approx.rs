@@ -0,0 +1,64 @@
+//! [Approximate nearest neighbor search](https://en.wikipedia.org/wiki/Nearest_neighbor_search#Approximation_methods).
+
+use num_traits::{one, Num};
+
+/// A view of an index that does `$(1 + \epsilon)$`-approximate nearest neighbor searches.
+///
+/// Subtrees are pruned as soon as `$(1 + \epsilon)$` times the lower bound on their distance from
+/// the target is out of range.  Every neighbor found is therefore at most `$(1 + \epsilon)$` times
+/// farther away than the true neighbor of the same rank, in any space where the underlying index
+/// is exact.
+///
+/// Implemented for [VP trees](crate::vp), where `$\epsilon$` is a distance value, and for [k-d
+/// trees](crate::kd), where it is a coordinate value.
+///
+///     use acap::approx::Approximate;
+///     use acap::euclid::Euclidean;
+///     use acap::vp::VpTree;
+///     use acap::NearestNeighbors;
+///
+///     let tree = VpTree::balanced(vec![
+///         Euclidean([3.0, 4.0]),
+///         Euclidean([5.0, 12.0]),
+///         Euclidean([8.0, 15.0]),
+///         Euclidean([7.0, 24.0]),
+///     ]);
+///
+///     let approx = Approximate::new(&tree, 0.5);
+///     let nearest = approx.nearest(&[7.0, 7.0]).unwrap();
+///     assert!(nearest.distance <= 1.5 * 5.0);
+#[derive(Debug)]
+pub struct Approximate<'a, I, E> {
+    /// The underlying index.
+    index: &'a I,
+    /// The approximation factor, `$1 + \epsilon$`.
+    factor: E,
+}
+
+impl<'a, I, E: Copy + Num> Approximate<'a, I, E> {
+    /// Create a new approximate view of an index.
+    ///
+    /// * `index`: The index to search.
+    /// * `epsilon`: The maximum relative error.
+    pub fn new(index: &'a I, epsilon: E) -> Self {
+        Self {
+            index,
+            factor: one::<E>() + epsilon,
+        }
+    }
+
+    /// Get the underlying index.
+    pub fn index(&self) -> &'a I {
+        self.index
+    }
+
+    /// Get the maximum relative error.
+    pub fn epsilon(&self) -> E {
+        self.factor - one()
+    }
+
+    /// Get the approximation factor, `$1 + \epsilon$`.
+    pub(crate) fn factor(&self) -> E {
+        self.factor
+    }
+}
@@ -0,0 +1,700 @@
+//! [Ball trees](https://en.wikipedia.org/wiki/Ball_tree).
+
+use crate::chebyshev::{chebyshev_distance, Chebyshev};
+use crate::coords::Coordinates;
+use crate::distance::{Distance, DistanceValue, Proximity, Value};
+use crate::euclid::{euclidean_distance, Euclidean, EuclideanDistance};
+use crate::lp::Minkowski;
+use crate::taxi::{taxicab_distance, Taxicab};
+use crate::util::Ordered;
+use crate::{ExactNeighbors, NearestNeighbors, Neighborhood};
+
+use num_traits::{one, zero};
+
+use std::fmt::{self, Debug, Formatter};
+use std::iter::{Extend, FromIterator};
+use std::ops::Deref;
+
+/// The maximum number of items in a leaf bucket.
+const BUCKET_SIZE: usize = 16;
+
+/// A [`Proximity`] over a [coordinate space] that can measure distances from arbitrary centroids.
+///
+/// [coordinate space]: Coordinates
+pub trait BallProximity: Coordinates + Proximity {
+    /// Compute the distance between a centroid and a point.
+    fn centroid_distance<T>(centroid: &[Self::Value], point: &T) -> DistanceValue<Self>
+    where
+        T: ?Sized + Coordinates<Value = Self::Value>;
+}
+
+/// Blanket [`BallProximity`] implementation for references.
+impl<T: BallProximity> BallProximity for &T {
+    fn centroid_distance<U>(centroid: &[Self::Value], point: &U) -> DistanceValue<Self>
+    where
+        U: ?Sized + Coordinates<Value = Self::Value>,
+    {
+        T::centroid_distance(centroid, point)
+    }
+}
+
+impl<T> BallProximity for Euclidean<T>
+where
+    T: Coordinates,
+    EuclideanDistance<T::Value>: Distance,
+{
+    fn centroid_distance<U>(centroid: &[Self::Value], point: &U) -> DistanceValue<Self>
+    where
+        U: ?Sized + Coordinates<Value = Self::Value>,
+    {
+        euclidean_distance(centroid, point).value()
+    }
+}
+
+impl<T: Coordinates> BallProximity for Taxicab<T> {
+    fn centroid_distance<U>(centroid: &[Self::Value], point: &U) -> DistanceValue<Self>
+    where
+        U: ?Sized + Coordinates<Value = Self::Value>,
+    {
+        taxicab_distance(centroid, point)
+    }
+}
+
+impl<T: Coordinates> BallProximity for Chebyshev<T> {
+    fn centroid_distance<U>(centroid: &[Self::Value], point: &U) -> DistanceValue<Self>
+    where
+        U: ?Sized + Coordinates<Value = Self::Value>,
+    {
+        chebyshev_distance(centroid, point)
+    }
+}
+
+/// Compute the point halfway between two values, without overflowing.
+fn midpoint<V: Value>(min: V, max: V) -> V {
+    let two = one::<V>() + one();
+    if (min < zero()) == (max < zero()) {
+        min + (max - min) / two
+    } else {
+        (min + max) / two
+    }
+}
+
+/// Compute the centroid of some items.
+///
+/// This is the center of their bounding box rather than their mean, since summing the coordinates
+/// (or even counting the items) could overflow an integer [`Coordinates::Value`].
+fn centroid<T: Coordinates>(items: &[T]) -> Vec<T::Value> {
+    let dims = items.first().map_or(0, T::dims);
+    (0..dims)
+        .map(|i| {
+            let mut min = items[0].coord(i);
+            let mut max = min;
+            for item in items {
+                let coord = item.coord(i);
+                if coord < min {
+                    min = coord;
+                }
+                if coord > max {
+                    max = coord;
+                }
+            }
+            midpoint(min, max)
+        })
+        .collect()
+}
+
+/// Compute the radius of the ball around a centroid that covers some items.
+fn covering_radius<T: BallProximity>(centroid: &[T::Value], items: &[T]) -> DistanceValue<T> {
+    let mut radius = zero();
+    for item in items {
+        let distance = T::centroid_distance(centroid, item);
+        if distance > radius {
+            radius = distance;
+        }
+    }
+    radius
+}
+
+/// Partition some items in half, along the dimension where they are most spread out.
+///
+/// Returns the index of the first item in the second half.
+fn split<T: Coordinates>(items: &mut [T]) -> usize {
+    let dims = items[0].dims();
+
+    let mut widest = 0;
+    let mut max_spread = zero();
+    for i in 0..dims {
+        let mut min = items[0].coord(i);
+        let mut max = min;
+        for item in items.iter() {
+            let coord = item.coord(i);
+            if coord < min {
+                min = coord;
+            }
+            if coord > max {
+                max = coord;
+            }
+        }
+
+        let spread = max - min;
+        if spread > max_spread {
+            widest = i;
+            max_spread = spread;
+        }
+    }
+
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by_key(mid, |x| Ordered::new(x.coord(widest)));
+    mid
+}
+
+/// A node in a ball tree.
+#[derive(Debug)]
+struct BallNode<T: Coordinates, R = DistanceValue<T>> {
+    /// The center of this node's ball.
+    centroid: Vec<T::Value>,
+    /// The radius of this node's ball.
+    radius: R,
+    /// The items in this node, if it is a leaf.
+    bucket: Vec<T>,
+    /// The left subtree, if any.
+    left: Option<Box<Self>>,
+    /// The right subtree, if any.
+    right: Option<Box<Self>>,
+}
+
+impl<T: BallProximity> BallNode<T> {
+    /// Create a balanced tree.
+    fn balanced<I: IntoIterator<Item = T>>(items: I) -> Option<Self> {
+        let items: Vec<_> = items.into_iter().collect();
+        if items.is_empty() {
+            None
+        } else {
+            Some(Self::balanced_recursive(items))
+        }
+    }
+
+    /// Create a balanced subtree.
+    fn balanced_recursive(items: Vec<T>) -> Self {
+        let centroid = centroid(&items);
+        let radius = covering_radius(&centroid, &items);
+
+        let mut node = Self {
+            centroid,
+            radius,
+            bucket: items,
+            left: None,
+            right: None,
+        };
+        node.split_bucket();
+        node
+    }
+
+    /// Split this node's bucket into two subtrees, if it is too large.
+    fn split_bucket(&mut self) {
+        if self.bucket.len() > BUCKET_SIZE {
+            let mut left = std::mem::take(&mut self.bucket);
+            let mid = split(&mut left);
+            let right = left.split_off(mid);
+
+            self.left = Some(Box::new(Self::balanced_recursive(left)));
+            self.right = Some(Box::new(Self::balanced_recursive(right)));
+        }
+    }
+
+    /// Push a new item into this subtree.
+    fn push(&mut self, item: T) {
+        let distance = T::centroid_distance(&self.centroid, &item);
+        if distance > self.radius {
+            self.radius = distance;
+        }
+
+        match (&mut self.left, &mut self.right) {
+            (Some(left), Some(right)) => {
+                let dl = T::centroid_distance(&left.centroid, &item);
+                let dr = T::centroid_distance(&right.centroid, &item);
+                if dl <= dr {
+                    left.push(item);
+                } else {
+                    right.push(item);
+                }
+            }
+            _ => {
+                self.bucket.push(item);
+                self.split_bucket();
+            }
+        }
+    }
+
+    /// Move all the items from this subtree into a vector.
+    fn drain(self, items: &mut Vec<T>) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            items.extend(node.bucket);
+            stack.extend(node.left.map(|left| *left));
+            stack.extend(node.right.map(|right| *right));
+        }
+    }
+}
+
+trait BallSearch<'a, K, V, N>: Copy
+where
+    K: Proximity<&'a V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: 'a + BallProximity,
+    N: Neighborhood<K, &'a V>,
+{
+    /// Get the center of this node's ball.
+    fn centroid(self) -> &'a [V::Value];
+
+    /// Get the radius of this node's ball.
+    fn radius(self) -> DistanceValue<V>;
+
+    /// Get the items in this node, if it is a leaf.
+    fn bucket(self) -> &'a [V];
+
+    /// Get the left and right subtrees, unless this node is a leaf.
+    fn children(self) -> Option<(Self, Self)>;
+
+    /// Recursively search for nearest neighbors.
+    fn search(self, neighborhood: &mut N) {
+        if let Some((left, right)) = self.children() {
+            let target = neighborhood.target();
+            let dl = V::centroid_distance(left.centroid(), &target);
+            let dr = V::centroid_distance(right.centroid(), &target);
+
+            let ((near, dn), (far, df)) = if dl <= dr {
+                ((left, dl), (right, dr))
+            } else {
+                ((right, dr), (left, dl))
+            };
+
+            if neighborhood.contains(dn - near.radius()) {
+                near.search(neighborhood);
+            }
+            if neighborhood.contains(df - far.radius()) {
+                far.search(neighborhood);
+            }
+        } else {
+            for item in self.bucket() {
+                if neighborhood.is_exhausted() {
+                    break;
+                }
+                neighborhood.consider_bounded(item);
+            }
+        }
+    }
+}
+
+impl<'a, K, V, N> BallSearch<'a, K, V, N> for &'a BallNode<V>
+where
+    K: Proximity<&'a V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: 'a + BallProximity,
+    N: Neighborhood<K, &'a V>,
+{
+    fn centroid(self) -> &'a [V::Value] {
+        &self.centroid
+    }
+
+    fn radius(self) -> DistanceValue<V> {
+        self.radius
+    }
+
+    fn bucket(self) -> &'a [V] {
+        &self.bucket
+    }
+
+    fn children(self) -> Option<(Self, Self)> {
+        match (&self.left, &self.right) {
+            (Some(left), Some(right)) => Some((left.deref(), right.deref())),
+            _ => None,
+        }
+    }
+}
+
+/// A [ball tree](https://en.wikipedia.org/wiki/Ball_tree).
+///
+/// Each node of a ball tree holds the centroid and covering radius of the items beneath it, and the
+/// leaves hold small buckets of items.  Balls bound the distance to every item in a subtree much
+/// more tightly than the axis-aligned splits of a [k-d tree](crate::kd::KdTree), especially in
+/// moderate dimensions.
+pub struct BallTree<T: Coordinates + Proximity> {
+    root: Option<BallNode<T>>,
+}
+
+impl<T: BallProximity> BallTree<T> {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self { root: None }
+    }
+
+    /// Create a balanced tree out of a sequence of items.
+    pub fn balanced<I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self {
+            root: BallNode::balanced(items),
+        }
+    }
+
+    /// Rebalance this ball tree.
+    pub fn balance(&mut self) {
+        let mut items = Vec::new();
+        if let Some(root) = self.root.take() {
+            root.drain(&mut items);
+        }
+
+        self.root = BallNode::balanced(items);
+    }
+
+    /// Push a new item into the tree.
+    ///
+    /// Inserting elements individually tends to unbalance the tree.  Use [`BallTree::balanced()`]
+    /// if possible to create a balanced tree from a batch of items.
+    pub fn push(&mut self, item: T) {
+        if let Some(root) = &mut self.root {
+            root.push(item);
+        } else {
+            self.root = BallNode::balanced(Some(item));
+        }
+    }
+}
+
+// Can't derive(Debug) due to https://github.com/rust-lang/rust/issues/26925
+impl<T> Debug for BallTree<T>
+where
+    T: BallProximity + Debug,
+    T::Value: Debug,
+    DistanceValue<T>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("BallTree")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+impl<T: BallProximity> Default for BallTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: BallProximity> Extend<T> for BallTree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        if self.root.is_some() {
+            for item in items {
+                self.push(item);
+            }
+        } else {
+            self.root = BallNode::balanced(items);
+        }
+    }
+}
+
+impl<T: BallProximity> FromIterator<T> for BallTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::balanced(items)
+    }
+}
+
+/// An iterator that moves values out of a ball tree.
+#[derive(Debug)]
+pub struct IntoIter<T>(std::vec::IntoIter<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.next()
+    }
+}
+
+impl<T: BallProximity> IntoIterator for BallTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut items = Vec::new();
+        if let Some(root) = self.root {
+            root.drain(&mut items);
+        }
+        IntoIter(items.into_iter())
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for BallTree<V>
+where
+    K: Proximity<V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: BallProximity,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if let Some(root) = &self.root {
+            root.search(&mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// Ball trees are exact for [Minkowski] distances.
+impl<K, V> ExactNeighbors<K, V> for BallTree<V>
+where
+    K: Minkowski<V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: BallProximity + Minkowski,
+{}
+
+/// A node in a flat ball tree.
+#[derive(Debug)]
+struct FlatBallNode<T: Coordinates, R = DistanceValue<T>> {
+    /// The center of this node's ball.
+    centroid: Vec<T::Value>,
+    /// The radius of this node's ball.
+    radius: R,
+    /// The number of nodes in the left subtree.
+    left_nodes: usize,
+    /// The number of items in the left subtree.
+    left_items: usize,
+}
+
+impl<T: BallProximity> FlatBallNode<T> {
+    /// Create a balanced tree, permuting the items so each subtree holds a contiguous range.
+    fn balanced(items: &mut [T]) -> Vec<Self> {
+        let mut nodes = Vec::new();
+        if !items.is_empty() {
+            Self::balance_recursive(&mut nodes, items);
+        }
+        nodes
+    }
+
+    /// Create a balanced subtree.
+    fn balance_recursive(nodes: &mut Vec<Self>, items: &mut [T]) {
+        let centroid = centroid(items);
+        let radius = covering_radius(&centroid, items);
+
+        let index = nodes.len();
+        nodes.push(Self {
+            centroid,
+            radius,
+            left_nodes: 0,
+            left_items: 0,
+        });
+
+        if items.len() > BUCKET_SIZE {
+            let mid = split(items);
+            let (left, right) = items.split_at_mut(mid);
+
+            Self::balance_recursive(nodes, left);
+            nodes[index].left_nodes = nodes.len() - index - 1;
+            nodes[index].left_items = mid;
+            Self::balance_recursive(nodes, right);
+        }
+    }
+}
+
+/// A subtree of a flat ball tree.
+struct FlatBallSubtree<'a, T: Coordinates + Proximity> {
+    /// The nodes in this subtree.
+    nodes: &'a [FlatBallNode<T>],
+    /// The items in this subtree.
+    items: &'a [T],
+}
+
+// Can't derive(Clone, Copy) due to https://github.com/rust-lang/rust/issues/26925
+impl<'a, T: Coordinates + Proximity> Clone for FlatBallSubtree<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: Coordinates + Proximity> Copy for FlatBallSubtree<'a, T> {}
+
+impl<'a, K, V, N> BallSearch<'a, K, V, N> for FlatBallSubtree<'a, V>
+where
+    K: Proximity<&'a V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: 'a + BallProximity,
+    N: Neighborhood<K, &'a V>,
+{
+    fn centroid(self) -> &'a [V::Value] {
+        &self.nodes[0].centroid
+    }
+
+    fn radius(self) -> DistanceValue<V> {
+        self.nodes[0].radius
+    }
+
+    fn bucket(self) -> &'a [V] {
+        if self.nodes.len() == 1 {
+            self.items
+        } else {
+            &[]
+        }
+    }
+
+    fn children(self) -> Option<(Self, Self)> {
+        if self.nodes.len() == 1 {
+            return None;
+        }
+
+        let node = &self.nodes[0];
+        let (left_nodes, right_nodes) = self.nodes[1..].split_at(node.left_nodes);
+        let (left_items, right_items) = self.items.split_at(node.left_items);
+
+        let left = Self {
+            nodes: left_nodes,
+            items: left_items,
+        };
+        let right = Self {
+            nodes: right_nodes,
+            items: right_items,
+        };
+        Some((left, right))
+    }
+}
+
+/// A [ball tree] stored as a flat array.
+///
+/// A FlatBallTree is always balanced and usually more efficient than a [`BallTree`], but doesn't
+/// support dynamic updates.
+///
+/// [ball tree]: https://en.wikipedia.org/wiki/Ball_tree
+pub struct FlatBallTree<T: Coordinates + Proximity> {
+    nodes: Vec<FlatBallNode<T>>,
+    items: Vec<T>,
+}
+
+impl<T: BallProximity> FlatBallTree<T> {
+    /// Create a balanced tree out of a sequence of items.
+    pub fn balanced<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut items: Vec<_> = items.into_iter().collect();
+        let nodes = FlatBallNode::balanced(&mut items);
+        Self { nodes, items }
+    }
+}
+
+// Can't derive(Debug) due to https://github.com/rust-lang/rust/issues/26925
+impl<T> Debug for FlatBallTree<T>
+where
+    T: BallProximity + Debug,
+    T::Value: Debug,
+    DistanceValue<T>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("FlatBallTree")
+            .field("nodes", &self.nodes)
+            .field("items", &self.items)
+            .finish()
+    }
+}
+
+impl<T: BallProximity> FromIterator<T> for FlatBallTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::balanced(items)
+    }
+}
+
+/// An iterator that moves values out of a flat ball tree.
+#[derive(Debug)]
+pub struct FlatIntoIter<T>(std::vec::IntoIter<T>);
+
+impl<T> Iterator for FlatIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.next()
+    }
+}
+
+impl<T: Coordinates + Proximity> IntoIterator for FlatBallTree<T> {
+    type Item = T;
+    type IntoIter = FlatIntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        FlatIntoIter(self.items.into_iter())
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for FlatBallTree<V>
+where
+    K: Proximity<V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: BallProximity,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if !self.nodes.is_empty() {
+            let root = FlatBallSubtree {
+                nodes: &self.nodes,
+                items: &self.items,
+            };
+            root.search(&mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// Ball trees are exact for [Minkowski] distances.
+impl<K, V> ExactNeighbors<K, V> for FlatBallTree<V>
+where
+    K: Minkowski<V, Distance = V::Distance> + Coordinates<Value = V::Value>,
+    V: BallProximity + Minkowski,
+{}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::tests::test_exact_neighbors;
+    use crate::Neighbor;
+
+    #[test]
+    fn test_ball_tree() {
+        test_exact_neighbors(BallTree::from_iter);
+    }
+
+    #[test]
+    fn test_unbalanced_ball_tree() {
+        test_exact_neighbors(|points| {
+            let mut tree = BallTree::new();
+            for point in points {
+                tree.push(point);
+            }
+            tree
+        });
+    }
+
+    #[test]
+    fn test_flat_ball_tree() {
+        test_exact_neighbors(FlatBallTree::from_iter);
+    }
+
+    #[test]
+    fn test_integer_ball_tree() {
+        let points: Vec<_> = (0..4).map(|i| Euclidean([i32::MAX - i, 0])).collect();
+        let tree = BallTree::balanced(points);
+        let nearest = tree.nearest(&Euclidean([i32::MAX, 1])).unwrap();
+        assert_eq!(nearest.item, &Euclidean([i32::MAX, 0]));
+        assert_eq!(nearest.distance, 1);
+
+        let points: Vec<_> = (0..200)
+            .map(|i| Chebyshev([(i / 4 - 25) as i8, (i % 50 - 25) as i8]))
+            .collect();
+        let exhaustive = ExhaustiveSearch::from_iter(points.clone());
+        let tree = BallTree::balanced(points.clone());
+        let flat = FlatBallTree::balanced(points);
+
+        let distances = |neighbors: Vec<Neighbor<_, i8>>| -> Vec<_> {
+            neighbors.iter().map(|n| n.distance).collect()
+        };
+        for target in &[Chebyshev([0, 0]), Chebyshev([-30, 30]), Chebyshev([100, -100])] {
+            let expected = distances(exhaustive.k_nearest(target, 5));
+            assert_eq!(distances(tree.k_nearest(target, 5)), expected);
+            assert_eq!(distances(flat.k_nearest(target, 5)), expected);
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! [BK-trees](https://en.wikipedia.org/wiki/BK-tree).
+
+use crate::distance::{Metric, Proximity};
+use crate::{ExactNeighbors, NearestNeighbors, Neighborhood};
+
+use num_traits::PrimInt;
+
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fmt::{self, Debug, Formatter};
+use std::iter::{Extend, FromIterator};
+use std::ops::Bound;
+
+/// A node in a BK-tree.
+#[derive(Debug)]
+struct BkNode<T: Proximity> {
+    /// The item stored in this node.
+    item: T,
+    /// The children of this node, keyed by their distance from it.
+    children: BTreeMap<T::Distance, Self>,
+}
+
+impl<T> BkNode<T>
+where
+    T: Proximity,
+    T::Distance: PrimInt,
+{
+    /// Create a new BkNode.
+    fn new(item: T) -> Self {
+        Self {
+            item,
+            children: BTreeMap::new(),
+        }
+    }
+
+    /// Push a new item into this subtree.
+    fn push(&mut self, item: T) {
+        let mut node = self;
+        loop {
+            let distance = node.item.distance(&item);
+            match node.children.entry(distance) {
+                Entry::Occupied(child) => {
+                    node = child.into_mut();
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Self::new(item));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Recursively search for nearest neighbors.
+    fn search<'a, K, N>(&'a self, neighborhood: &mut N)
+    where
+        K: Proximity<&'a T, Distance = T::Distance>,
+        N: Neighborhood<K, &'a T>,
+    {
+        if neighborhood.is_exhausted() {
+            return;
+        }
+
+        let distance = neighborhood.consider(&self.item);
+
+        // Every descendant of the child with key k is at least |distance - k| from the target, so
+        // visit the children in order of that bound, and stop at the first one out of range
+        let mut below = self.children.range(..=distance).rev().peekable();
+        let mut above = self
+            .children
+            .range((Bound::Excluded(distance), Bound::Unbounded))
+            .peekable();
+
+        loop {
+            let next = match (below.peek(), above.peek()) {
+                (Some(&(&lo, _)), Some(&(&hi, _))) => {
+                    if distance - lo <= hi - distance {
+                        below.next()
+                    } else {
+                        above.next()
+                    }
+                }
+                (Some(_), None) => below.next(),
+                (None, Some(_)) => above.next(),
+                (None, None) => None,
+            };
+
+            let (&key, child) = match next {
+                Some(next) => next,
+                None => break,
+            };
+
+            let bound = if key > distance {
+                key - distance
+            } else {
+                distance - key
+            };
+            if !neighborhood.contains(bound) {
+                break;
+            }
+
+            child.search(neighborhood);
+        }
+    }
+}
+
+/// A [Burkhard-Keller tree](https://en.wikipedia.org/wiki/BK-tree).
+///
+/// BK-trees index [metric spaces](Metric) with integer distances, such as [Hamming
+/// space](crate::hamming::Hamming) or edit distances.  Each node has at most one child for every
+/// possible distance, so they work best when the distances take few distinct values.
+pub struct BkTree<T: Proximity> {
+    root: Option<BkNode<T>>,
+    len: usize,
+}
+
+// Can't derive(Debug) due to https://github.com/rust-lang/rust/issues/26925
+impl<T> Debug for BkTree<T>
+where
+    T: Proximity + Debug,
+    T::Distance: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("BkTree")
+            .field("root", &self.root)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<T> BkTree<T>
+where
+    T: Proximity,
+    T::Distance: PrimInt,
+{
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            len: 0,
+        }
+    }
+
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Push a new item into the tree.
+    pub fn push(&mut self, item: T) {
+        if let Some(root) = &mut self.root {
+            root.push(item);
+        } else {
+            self.root = Some(BkNode::new(item));
+        }
+        self.len += 1;
+    }
+}
+
+impl<T> Default for BkTree<T>
+where
+    T: Proximity,
+    T::Distance: PrimInt,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for BkTree<T>
+where
+    T: Proximity,
+    T::Distance: PrimInt,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for BkTree<T>
+where
+    T: Proximity,
+    T::Distance: PrimInt,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(items);
+        tree
+    }
+}
+
+/// An iterator that moves values out of a BK-tree.
+pub struct IntoIter<T: Proximity> {
+    stack: Vec<BkNode<T>>,
+}
+
+impl<T> Debug for IntoIter<T>
+where
+    T: Proximity + Debug,
+    T::Distance: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("IntoIter")
+            .field("stack", &self.stack)
+            .finish()
+    }
+}
+
+impl<T: Proximity> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stack.pop().map(|node| {
+            self.stack.extend(node.children.into_values());
+            node.item
+        })
+    }
+}
+
+impl<T: Proximity> IntoIterator for BkTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            stack: self.root.into_iter().collect(),
+        }
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for BkTree<V>
+where
+    K: Metric<V, Distance = V::Distance>,
+    V: Metric,
+    V::Distance: PrimInt,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if let Some(root) = &self.root {
+            root.search(&mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// BK-trees are exact for [metric spaces](Metric).
+impl<K, V> ExactNeighbors<K, V> for BkTree<V>
+where
+    K: Metric<V, Distance = V::Distance>,
+    V: Metric,
+    V::Distance: PrimInt,
+{}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::hamming::Hamming;
+
+    use rand::prelude::*;
+
+    #[test]
+    fn test_empty() {
+        let tree: BkTree<Hamming<u32>> = BkTree::new();
+        let target = Hamming(0);
+        assert_eq!(tree.nearest(&target), None);
+        assert!(tree.k_nearest(&target, 3).is_empty());
+    }
+
+    #[test]
+    fn test_bk_tree() {
+        let points: Vec<_> = (0..1000).map(|_| Hamming(random::<u32>())).collect();
+        let tree = BkTree::from_iter(points.clone());
+        let eindex = ExhaustiveSearch::from_iter(points.clone());
+        assert_eq!(tree.len(), 1000);
+
+        // Ties make the neighbors themselves ambiguous, so just compare the distances
+        let target = Hamming(random());
+        let distances: Vec<_> = tree.k_nearest(&target, 5).iter().map(|n| n.distance).collect();
+        let expected: Vec<_> = eindex.k_nearest(&target, 5).iter().map(|n| n.distance).collect();
+        assert_eq!(distances, expected);
+
+        let within = tree.k_nearest_within(&target, 1000, 12);
+        let expected = eindex.k_nearest_within(&target, 1000, 12);
+        assert_eq!(within.len(), expected.len());
+
+        for point in &points {
+            let nearest = tree.nearest(point).expect("No nearest neighbor found");
+            assert_eq!(nearest.distance, 0);
+        }
+    }
+}
@@ -6,6 +6,9 @@ use crate::lp::Minkowski;
 
 use num_traits::{zero, Signed};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// A point in Chebyshev space.
 ///
 /// This wrapper equips any [coordinate space] with the [Chebyshev distance] metric.
@@ -13,6 +16,8 @@ use num_traits::{zero, Signed};
 /// [coordinate space]: Coordinates
 /// [Chebyshev distance]: chebyshev_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[repr(transparent)]
 pub struct Chebyshev<T>(pub T);
 
 impl<T> Chebyshev<T> {
@@ -6,6 +6,9 @@ use crate::distance::{Distance, Metric, Proximity, Value};
 use num_traits::real::Real;
 use num_traits::{one, zero};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 
@@ -73,8 +76,21 @@ where
 /// [coordinate space]: Coordinates
 /// [cosine distance]: cosine_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Cosine<T>(pub T);
 
+impl<T: Coordinates> Coordinates for Cosine<T> {
+    type Value = T::Value;
+
+    fn dims(&self) -> usize {
+        self.0.dims()
+    }
+
+    fn coord(&self, i: usize) -> Self::Value {
+        self.0.coord(i)
+    }
+}
+
 impl<T> Proximity for Cosine<T>
 where
     T: Coordinates,
@@ -170,8 +186,21 @@ where
 /// [coordinate space]: Coordinates
 /// [cosine distance]: prenorm_cosine_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct PrenormCosine<T>(pub T);
 
+impl<T: Coordinates> Coordinates for PrenormCosine<T> {
+    type Value = T::Value;
+
+    fn dims(&self) -> usize {
+        self.0.dims()
+    }
+
+    fn coord(&self, i: usize) -> Self::Value {
+        self.0.coord(i)
+    }
+}
+
 impl<T> Proximity for PrenormCosine<T>
 where
     T: Coordinates,
@@ -234,8 +263,21 @@ where
 /// [coordinate space]: Coordinates
 /// [angular distance]: angular_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Angular<T>(pub T);
 
+impl<T: Coordinates> Coordinates for Angular<T> {
+    type Value = T::Value;
+
+    fn dims(&self) -> usize {
+        self.0.dims()
+    }
+
+    fn coord(&self, i: usize) -> Self::Value {
+        self.0.coord(i)
+    }
+}
+
 impl<T> Proximity for Angular<T>
 where
     T: Coordinates,
@@ -326,8 +368,21 @@ where
 /// [coordinate space]: Coordinates
 /// [angular distance]: prenorm_angular_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct PrenormAngular<T>(pub T);
 
+impl<T: Coordinates> Coordinates for PrenormAngular<T> {
+    type Value = T::Value;
+
+    fn dims(&self) -> usize {
+        self.0.dims()
+    }
+
+    fn coord(&self, i: usize) -> Self::Value {
+        self.0.coord(i)
+    }
+}
+
 impl<T> Proximity for PrenormAngular<T>
 where
     T: Coordinates,
@@ -406,6 +461,7 @@ where
 ///
 /// [angular distance]: https://en.wikipedia.org/wiki/Cosine_similarity#Angular_distance_and_similarity
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct AngularDistance<T>(T);
 
 impl<T: Real + Value> AngularDistance<T> {
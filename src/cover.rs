@@ -0,0 +1,466 @@
+//! [Cover trees](https://en.wikipedia.org/wiki/Cover_tree).
+
+use crate::distance::{DistanceValue, Metric, Proximity};
+use crate::util::Ordered;
+use crate::{ExactNeighbors, NearestNeighbors, Neighborhood};
+
+use num_traits::{one, zero};
+
+use std::fmt::{self, Debug, Formatter};
+use std::iter::{Extend, FromIterator};
+
+/// A node in a cover tree.
+#[derive(Debug)]
+struct CoverNode<T, R = DistanceValue<T>> {
+    /// The item stored in this node.
+    item: T,
+    /// The covering radius of this node, which halves at every level of a tree built by insertion.
+    radius: R,
+    /// An upper bound on the distance from this node to any of its descendants.
+    maxdist: R,
+    /// The children of this node.
+    children: Vec<Self>,
+}
+
+impl<T: Proximity> CoverNode<T> {
+    /// Create a new CoverNode.
+    fn new(item: T, radius: DistanceValue<T>) -> Self {
+        Self {
+            item,
+            radius,
+            maxdist: zero(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Get the distance from this node to an item.
+    fn distance_to(&self, item: &T) -> DistanceValue<T> {
+        self.item.distance(item).into()
+    }
+
+    /// Insert an item into this subtree, given its distance from this node.
+    ///
+    /// The item must be within the covering radius of this node.
+    fn insert(&mut self, item: T, distance: DistanceValue<T>) {
+        self.insert_node(Self::new(item, zero()), distance);
+    }
+
+    /// Insert a whole subtree into this subtree, given the distance from this node to its root.
+    ///
+    /// The subtree is attached as deep as possible without shrinking its covering radius, so its
+    /// own children stay covered.  Single items can go anywhere, so inserting them keeps the
+    /// children of every node separated, but whole subtrees may end up close to their siblings.
+    fn insert_node(&mut self, mut node: Self, distance: DistanceValue<T>) {
+        let maxdist = distance + node.maxdist;
+        if maxdist > self.maxdist {
+            self.maxdist = maxdist;
+        }
+
+        // Descend into the nearest child that covers the node, if any
+        let two = one::<DistanceValue<T>>() + one();
+        let mut nearest: Option<(usize, DistanceValue<T>)> = None;
+        for (i, child) in self.children.iter().enumerate() {
+            if child.radius < two * node.radius {
+                continue;
+            }
+            let d = child.distance_to(&node.item);
+            if d <= child.radius && nearest.iter().all(|&(_, n)| d < n) {
+                nearest = Some((i, d));
+            }
+        }
+
+        if let Some((i, d)) = nearest {
+            self.children[i].insert_node(node, d);
+        } else {
+            let radius = self.radius / two;
+            if radius > node.radius {
+                node.radius = radius;
+            }
+            self.children.push(node);
+        }
+    }
+
+    /// Remove and return any leaf from this subtree, other than this node itself.
+    fn pop_leaf(&mut self) -> Option<Self> {
+        let child = self.children.last_mut()?;
+        if child.children.is_empty() {
+            self.children.pop()
+        } else {
+            child.pop_leaf()
+        }
+    }
+
+    /// Find and detach the node holding an item, from the descendants of this node.
+    fn remove(&mut self, item: &T) -> Option<Self>
+    where
+        T: PartialEq,
+    {
+        if let Some(i) = self.children.iter().position(|child| child.item == *item) {
+            return Some(self.children.swap_remove(i));
+        }
+
+        self.children
+            .iter_mut()
+            .filter(|child| child.distance_to(item) <= child.maxdist)
+            .find_map(|child| child.remove(item))
+    }
+
+    /// Recursively search the descendants of this node for nearest neighbors.
+    fn search<'a, K, N>(&'a self, neighborhood: &mut N)
+    where
+        K: Proximity<&'a T, Distance = T::Distance>,
+        N: Neighborhood<K, &'a T>,
+    {
+        let mut children = Vec::with_capacity(self.children.len());
+        for child in &self.children {
+            if neighborhood.is_exhausted() {
+                return;
+            }
+            children.push((neighborhood.consider(&child.item).into(), child));
+        }
+        children.sort_by_key(|&(d, _)| Ordered::new(d));
+
+        for (distance, child) in children {
+            if neighborhood.contains(distance - child.maxdist) {
+                child.search(neighborhood);
+            }
+        }
+    }
+}
+
+/// A [cover tree](https://en.wikipedia.org/wiki/Cover_tree).
+///
+/// In a tree built by [`push()`](Self::push), every node covers its children, which are within its
+/// covering radius, the covering radius halves at every level of the tree, and the children of each
+/// node are separated from each other by more than their own covering radius.  In spaces with a
+/// small [expansion constant] `$c$`, searches of such a tree take `$O(c^{12} \log n)$` time.  This
+/// implementation follows the [simplified cover tree] of Izbicki and Shelton, which supports
+/// efficient insertion.
+///
+/// [`remove()`](Self::remove) keeps searches exact, since they only rely on an upper bound on the
+/// distance to each node's descendants.  But it doesn't restore the other invariants, so the time
+/// bound no longer holds after removals.  Collect the remaining items into a new tree to restore
+/// it.
+///
+/// [expansion constant]: https://en.wikipedia.org/wiki/Cover_tree
+/// [simplified cover tree]: http://proceedings.mlr.press/v37/izbicki15.pdf
+pub struct CoverTree<T: Proximity> {
+    root: Option<CoverNode<T>>,
+    len: usize,
+}
+
+impl<T: Proximity> CoverTree<T> {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            len: 0,
+        }
+    }
+
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Push a new item into the tree.
+    pub fn push(&mut self, item: T) {
+        self.len += 1;
+
+        let mut root = if let Some(root) = self.root.take() {
+            root
+        } else {
+            self.root = Some(CoverNode::new(item, zero()));
+            return;
+        };
+
+        let mut distance = root.distance_to(&item);
+        let arbitrary = root.children.is_empty() || root.radius == zero::<DistanceValue<T>>();
+        if distance > root.radius && arbitrary {
+            // The covering radius of the root is arbitrary until it has children
+            root.radius = distance;
+        }
+
+        if distance <= root.radius {
+            root.insert(item, distance);
+            self.root = Some(root);
+            return;
+        }
+
+        // Promote leaves to the root until the new item is close enough to become the new root
+        let two = one::<DistanceValue<T>>() + one();
+        while distance > two * root.radius {
+            if let Some(mut leaf) = root.pop_leaf() {
+                let d = leaf.distance_to(&root.item);
+                leaf.radius = two * root.radius;
+                leaf.maxdist = d + root.maxdist;
+                leaf.children.push(root);
+                root = leaf;
+                distance = root.distance_to(&item);
+            } else {
+                root.radius *= two;
+            }
+        }
+
+        let mut node = CoverNode::new(item, two * root.radius);
+        node.maxdist = distance + root.maxdist;
+        node.children.push(root);
+        self.root = Some(node);
+    }
+
+    /// Remove an item from the tree, if it is present.
+    ///
+    /// The children of the removed node are reinserted into the tree as whole subtrees, without
+    /// changing their levels.  If the root is removed, one of its children takes its place.  The
+    /// tree still gives exact results, but without the time bound of a tree built by insertion.
+    pub fn remove(&mut self, item: &T) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let root = self.root.as_mut()?;
+
+        let node = if root.item == *item {
+            let mut node = self.root.take().unwrap();
+            if let Some(mut child) = node.children.pop() {
+                child.radius = node.radius;
+                child.maxdist = child.distance_to(&node.item) + node.maxdist;
+                self.root = Some(child);
+            }
+            node
+        } else {
+            root.remove(item)?
+        };
+
+        let CoverNode { item, children, .. } = node;
+        if let Some(root) = &mut self.root {
+            for child in children {
+                let distance = root.distance_to(&child.item);
+                root.insert_node(child, distance);
+            }
+        }
+
+        self.len -= 1;
+        Some(item)
+    }
+}
+
+// Can't derive(Debug) due to https://github.com/rust-lang/rust/issues/26925
+impl<T> Debug for CoverTree<T>
+where
+    T: Proximity + Debug,
+    DistanceValue<T>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("CoverTree")
+            .field("root", &self.root)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<T: Proximity> Default for CoverTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Proximity> Extend<T> for CoverTree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Proximity> FromIterator<T> for CoverTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(items);
+        tree
+    }
+}
+
+/// An iterator that moves values out of a cover tree.
+pub struct IntoIter<T: Proximity> {
+    stack: Vec<CoverNode<T>>,
+}
+
+impl<T> Debug for IntoIter<T>
+where
+    T: Proximity + Debug,
+    DistanceValue<T>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("IntoIter")
+            .field("stack", &self.stack)
+            .finish()
+    }
+}
+
+impl<T: Proximity> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stack.pop().map(|node| {
+            self.stack.extend(node.children);
+            node.item
+        })
+    }
+}
+
+impl<T: Proximity> IntoIterator for CoverTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            stack: self.root.into_iter().collect(),
+        }
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for CoverTree<V>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if let Some(root) = &self.root {
+            if !neighborhood.is_exhausted() {
+                neighborhood.consider_bounded(&root.item);
+                root.search(&mut neighborhood);
+            }
+        }
+        neighborhood
+    }
+}
+
+/// Cover trees are exact for [metric spaces](Metric).
+impl<K, V> ExactNeighbors<K, V> for CoverTree<V>
+where
+    K: Metric<V, Distance = V::Distance>,
+    V: Metric,
+{}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::euclid::Euclidean;
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::hamming::Hamming;
+    use crate::tests::test_exact_neighbors;
+
+    use rand::prelude::*;
+
+    #[test]
+    fn test_cover_tree() {
+        test_exact_neighbors(CoverTree::from_iter);
+    }
+
+    #[test]
+    fn test_hamming_cover_tree() {
+        let points: Vec<_> = (0..256).map(|_| Hamming(random::<u16>())).collect();
+        let tree = CoverTree::from_iter(points.clone());
+        let eindex = ExhaustiveSearch::from_iter(points);
+
+        // Ties make the neighbors themselves ambiguous, so just compare the distances
+        let target = Hamming(random());
+        let distances: Vec<_> = tree.k_nearest(&target, 5).iter().map(|n| n.distance).collect();
+        let expected: Vec<_> = eindex.k_nearest(&target, 5).iter().map(|n| n.distance).collect();
+        assert_eq!(distances, expected);
+    }
+
+    #[test]
+    fn test_cover_tree_remove() {
+        let points: Vec<_> = (0..256)
+            .map(|_| Euclidean([random::<f32>(), random(), random()]))
+            .collect();
+
+        let mut tree = CoverTree::from_iter(points.clone());
+        for point in points.iter().step_by(2) {
+            assert_eq!(tree.remove(point), Some(*point));
+        }
+        assert_eq!(tree.remove(&points[0]), None);
+        assert_eq!(tree.len(), 128);
+
+        let eindex = ExhaustiveSearch::from_iter(points.into_iter().skip(1).step_by(2));
+
+        let target = Euclidean([random(), random(), random()]);
+        assert_eq!(tree.k_nearest(&target, 3), eindex.k_nearest(&target, 3));
+    }
+
+    #[test]
+    fn test_cover_tree_remove_root() {
+        let points: Vec<_> = (0..1024)
+            .map(|_| Euclidean([random::<f32>(), random(), random()]))
+            .collect();
+
+        let mut tree = CoverTree::from_iter(points.clone());
+        let mut eindex = ExhaustiveSearch::from_iter(points);
+        let mut removed = Vec::new();
+        for _ in 0..100 {
+            let root = tree.root.as_ref().unwrap().item;
+            assert_eq!(tree.remove(&root), Some(root));
+            assert_eq!(eindex.remove(&root), Some(root));
+            removed.push(root);
+
+            let target = Euclidean([random(), random(), random()]);
+            assert_eq!(tree.k_nearest(&target, 3), eindex.k_nearest(&target, 3));
+        }
+        assert_eq!(tree.len(), 924);
+
+        for point in removed.into_iter().step_by(2) {
+            tree.push(point);
+            eindex.push(point);
+        }
+        let target = Euclidean([random(), random(), random()]);
+        assert_eq!(tree.k_nearest(&target, 3), eindex.k_nearest(&target, 3));
+
+        assert_eq!(tree.len(), 974);
+        assert_eq!(tree.into_iter().count(), 974);
+    }
+
+    /// Check that a subtree satisfies the cover tree invariants, and return its items.
+    fn assert_invariants<T: Proximity + Debug>(node: &CoverNode<T>) -> Vec<&T>
+    where
+        DistanceValue<T>: Debug,
+    {
+        let mut items = vec![&node.item];
+        for (i, child) in node.children.iter().enumerate() {
+            // Leveling
+            assert_eq!(child.radius + child.radius, node.radius);
+            // Covering
+            assert!(node.distance_to(&child.item) <= node.radius, "{:?}", child.item);
+            // Separation
+            for sibling in &node.children[..i] {
+                assert!(sibling.distance_to(&child.item) > child.radius, "{:?}", child.item);
+            }
+            items.extend(assert_invariants(child));
+        }
+
+        for item in &items {
+            assert!(node.distance_to(item) <= node.maxdist, "{:?}", item);
+        }
+        items
+    }
+
+    #[test]
+    fn test_cover_tree_invariants() {
+        let points: Vec<_> = (0..1024)
+            .map(|_| Euclidean([random::<f64>(), random(), random()]))
+            .collect();
+        let tree = CoverTree::from_iter(points);
+        assert_eq!(assert_invariants(tree.root.as_ref().unwrap()).len(), 1024);
+    }
+}
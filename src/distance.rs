@@ -106,15 +106,27 @@ impl<T: Value> Distance for T {
 /// ```
 ///
 /// With those implementations available, you could use a [`NearestNeighbors<Gps, PointOfInterest>`]
-/// instance to find the closest point(s) of interest to any GPS location.
+/// instance to find the closest point(s) of interest to any GPS location.  (In practice, the
+/// [`LatLon`] type already implements the haversine distance.)
 ///
 /// [`NearestNeighbors<Gps, PointOfInterest>`]: super::NearestNeighbors
+/// [`LatLon`]: crate::geo::LatLon
 pub trait Proximity<T: ?Sized = Self> {
     /// The type that represents distances.
     type Distance: Distance;
 
     /// Calculate the distance between this point and another one.
     fn distance(&self, other: &T) -> Self::Distance;
+
+    /// Calculate the distance between this point and another one, if it is at most `bound`.
+    ///
+    /// Returns `None` if the distance is greater than `bound`.  Implementations can override this
+    /// to stop computing the distance as soon as it is known to exceed the bound, e.g. once a
+    /// partial sum grows too large.  By default, it is computed in full with
+    /// [`distance()`](Self::distance).
+    fn distance_bounded(&self, other: &T, bound: Self::Distance) -> Option<Self::Distance> {
+        Some(self.distance(other)).filter(|&d| d <= bound)
+    }
 }
 
 // See https://github.com/rust-lang/rust/issues/38078
@@ -128,6 +140,10 @@ impl<'k, 'v, K: Proximity<V>, V> Proximity<&'v V> for &'k K {
     fn distance(&self, other: &&'v V) -> Self::Distance {
         (*self).distance(*other)
     }
+
+    fn distance_bounded(&self, other: &&'v V, bound: Self::Distance) -> Option<Self::Distance> {
+        (*self).distance_bounded(*other, bound)
+    }
 }
 
 /// Marker trait for [metric spaces].
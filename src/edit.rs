@@ -0,0 +1,409 @@
+//! [Edit distances](https://en.wikipedia.org/wiki/Edit_distance).
+
+use crate::distance::{Metric, Proximity};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+use std::cmp;
+
+/// A sequence of symbols that can be compared by edit distance.
+///
+/// Strings are compared by their [`char`]s, and slices by their elements.
+pub trait Symbols {
+    /// The type of each symbol.
+    type Symbol: Clone + Eq;
+
+    /// Get the symbols of this sequence.
+    fn symbols(&self) -> Cow<'_, [Self::Symbol]>;
+
+    /// Get the bytes of this sequence, if each byte is exactly one symbol.
+    ///
+    /// Two sequences that both have bytes are compared byte-by-byte, without collecting their
+    /// [`symbols()`](Symbols::symbols).
+    fn ascii(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+impl Symbols for str {
+    type Symbol = char;
+
+    fn symbols(&self) -> Cow<'_, [char]> {
+        Cow::Owned(self.chars().collect())
+    }
+
+    fn ascii(&self) -> Option<&[u8]> {
+        Some(self.as_bytes()).filter(|bytes| bytes.is_ascii())
+    }
+}
+
+impl Symbols for String {
+    type Symbol = char;
+
+    fn symbols(&self) -> Cow<'_, [char]> {
+        self.as_str().symbols()
+    }
+
+    fn ascii(&self) -> Option<&[u8]> {
+        self.as_str().ascii()
+    }
+}
+
+impl<T: Clone + Eq> Symbols for [T] {
+    type Symbol = T;
+
+    fn symbols(&self) -> Cow<'_, [T]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<T: Clone + Eq, const N: usize> Symbols for [T; N] {
+    type Symbol = T;
+
+    fn symbols(&self) -> Cow<'_, [T]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl<T: Clone + Eq> Symbols for Vec<T> {
+    type Symbol = T;
+
+    fn symbols(&self) -> Cow<'_, [T]> {
+        Cow::Borrowed(self)
+    }
+}
+
+/// Blanket [`Symbols`] implementation for references.
+impl<T: ?Sized + Symbols> Symbols for &T {
+    type Symbol = T::Symbol;
+
+    fn symbols(&self) -> Cow<'_, [T::Symbol]> {
+        (*self).symbols()
+    }
+
+    fn ascii(&self) -> Option<&[u8]> {
+        (*self).ascii()
+    }
+}
+
+/// A string or other sequence, compared by [Levenshtein distance].
+///
+/// This wrapper equips any sequence of [`Symbols`] with the Levenshtein distance metric.
+///
+///     use acap::distance::Proximity;
+///     use acap::edit::Levenshtein;
+///
+///     let kitten = Levenshtein("kitten");
+///     assert_eq!(kitten.distance(&Levenshtein("sitting")), 3);
+///     assert_eq!(kitten.distance(&Levenshtein(String::from("mitten"))), 1);
+///
+/// [Levenshtein distance]: levenshtein_distance
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Levenshtein<T>(pub T);
+
+impl<T> Levenshtein<T> {
+    /// Wrap a sequence.
+    pub fn new(sequence: T) -> Self {
+        Self(sequence)
+    }
+
+    /// Unwrap a sequence.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// Compute the edit distance between two sequences, as long as it is at most `bound`.
+///
+/// This uses a single row of the usual dynamic programming table, restricted to the diagonal band
+/// that can possibly stay within the bound, and gives up as soon as a whole row exceeds it.
+fn levenshtein<T: Eq>(x: &[T], y: &[T], bound: usize) -> Option<usize> {
+    // Common prefixes and suffixes don't affect the distance
+    let prefix = x.iter().zip(y).take_while(|(a, b)| a == b).count();
+    let (x, y) = (&x[prefix..], &y[prefix..]);
+    let suffix = x
+        .iter()
+        .rev()
+        .zip(y.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (x, y) = (&x[..x.len() - suffix], &y[..y.len() - suffix]);
+
+    // Keep the row as short as possible
+    let (x, y) = if x.len() < y.len() { (y, x) } else { (x, y) };
+    if x.len() - y.len() > bound {
+        return None;
+    }
+
+    // Cells outside the band are all more than the bound
+    let bound = cmp::min(bound, x.len());
+    let out = bound + 1;
+
+    let mut row: Vec<_> = (0..=y.len()).map(|j| cmp::min(j, out)).collect();
+    for (i, a) in x.iter().enumerate() {
+        let i = i + 1;
+        let start = i.saturating_sub(bound);
+        let end = cmp::min(y.len(), i + bound);
+
+        let (mut diag, mut left) = if start == 0 {
+            let diag = row[0];
+            row[0] = i;
+            (diag, i)
+        } else {
+            let diag = row[start - 1];
+            row[start - 1] = out;
+            (diag, out)
+        };
+
+        let mut min = left;
+        for (j, b) in y.iter().enumerate().take(end).skip(start.saturating_sub(1)) {
+            let j = j + 1;
+            let up = row[j];
+            let cost = if a == b { 0 } else { 1 };
+            let value = cmp::min(cmp::min(up, left) + 1, diag + cost).min(out);
+            row[j] = value;
+            diag = up;
+            left = value;
+            min = cmp::min(min, value);
+        }
+
+        if min > bound {
+            return None;
+        }
+    }
+
+    Some(row[y.len()]).filter(|&d| d <= bound)
+}
+
+/// Compute the [Levenshtein distance] between two sequences.
+///
+/// This is the smallest number of single-symbol insertions, deletions, and substitutions that turn
+/// one sequence into the other.  It takes `$O(n \cdot m)$` time, but only `$O(\min(n, m))$` space.
+///
+/// [Levenshtein distance]: https://en.wikipedia.org/wiki/Levenshtein_distance
+pub fn levenshtein_distance<T: Eq>(x: &[T], y: &[T]) -> i32 {
+    let bound = cmp::max(x.len(), y.len());
+    levenshtein(x, y, bound).unwrap() as i32
+}
+
+/// Compute the [Levenshtein distance] between two sequences, if it is at most `bound`.
+///
+/// Only the diagonal band of width `$2 \cdot \mathrm{bound} + 1$` of the dynamic programming table
+/// is computed, and the computation stops early once the distance is known to exceed the bound.
+/// This makes it much faster than [`levenshtein_distance()`] for small bounds.
+///
+/// [Levenshtein distance]: https://en.wikipedia.org/wiki/Levenshtein_distance
+pub fn bounded_levenshtein_distance<T: Eq>(x: &[T], y: &[T], bound: i32) -> Option<i32> {
+    if bound < 0 {
+        None
+    } else {
+        levenshtein(x, y, bound as usize).map(|d| d as i32)
+    }
+}
+
+/// Compute the Levenshtein distance between two sequences of [`Symbols`], if it is at most
+/// `bound`, comparing bytes directly when possible.
+fn symbols_distance<T, U>(x: &T, y: &U, bound: Option<i32>) -> Option<i32>
+where
+    T: ?Sized + Symbols,
+    U: ?Sized + Symbols<Symbol = T::Symbol>,
+{
+    fn distance<S: Eq>(x: &[S], y: &[S], bound: Option<i32>) -> Option<i32> {
+        match bound {
+            Some(bound) => bounded_levenshtein_distance(x, y, bound),
+            None => Some(levenshtein_distance(x, y)),
+        }
+    }
+
+    match (x.ascii(), y.ascii()) {
+        (Some(x), Some(y)) => distance(x, y, bound),
+        _ => distance(&x.symbols(), &y.symbols(), bound),
+    }
+}
+
+/// The Levenshtein distance function.
+impl<T, U> Proximity<Levenshtein<U>> for Levenshtein<T>
+where
+    T: Symbols,
+    U: Symbols<Symbol = T::Symbol>,
+{
+    type Distance = i32;
+
+    fn distance(&self, other: &Levenshtein<U>) -> Self::Distance {
+        symbols_distance(&self.0, &other.0, None).unwrap()
+    }
+
+    fn distance_bounded(
+        &self,
+        other: &Levenshtein<U>,
+        bound: Self::Distance,
+    ) -> Option<Self::Distance> {
+        symbols_distance(&self.0, &other.0, Some(bound))
+    }
+}
+
+impl<T, U> Proximity<U> for Levenshtein<T>
+where
+    T: Symbols,
+    U: ?Sized + Symbols<Symbol = T::Symbol>,
+{
+    type Distance = i32;
+
+    fn distance(&self, other: &U) -> Self::Distance {
+        symbols_distance(&self.0, other, None).unwrap()
+    }
+
+    fn distance_bounded(&self, other: &U, bound: Self::Distance) -> Option<Self::Distance> {
+        symbols_distance(&self.0, other, Some(bound))
+    }
+}
+
+impl<T, U> Proximity<Levenshtein<U>> for T
+where
+    T: Symbols,
+    U: Symbols<Symbol = T::Symbol>,
+{
+    type Distance = i32;
+
+    fn distance(&self, other: &Levenshtein<U>) -> Self::Distance {
+        symbols_distance(self, &other.0, None).unwrap()
+    }
+
+    fn distance_bounded(
+        &self,
+        other: &Levenshtein<U>,
+        bound: Self::Distance,
+    ) -> Option<Self::Distance> {
+        symbols_distance(self, &other.0, Some(bound))
+    }
+}
+
+/// Levenshtein distance is a metric.
+impl<T, U> Metric<Levenshtein<U>> for Levenshtein<T>
+where
+    T: Symbols,
+    U: Symbols<Symbol = T::Symbol>,
+{}
+
+impl<T, U> Metric<U> for Levenshtein<T>
+where
+    T: Symbols,
+    U: ?Sized + Symbols<Symbol = T::Symbol>,
+{}
+
+impl<T, U> Metric<Levenshtein<U>> for T
+where
+    T: Symbols,
+    U: Symbols<Symbol = T::Symbol>,
+{}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bk::BkTree;
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::vp::VpTree;
+    use crate::{NearestNeighbors, Neighbor};
+
+    use rand::prelude::*;
+
+    use std::iter::FromIterator;
+
+    /// The textbook quadratic-space algorithm.
+    fn naive_distance<T: Eq>(x: &[T], y: &[T]) -> usize {
+        let mut table = vec![vec![0; y.len() + 1]; x.len() + 1];
+        for (i, row) in table.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in table[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+        for i in 1..=x.len() {
+            for j in 1..=y.len() {
+                let cost = if x[i - 1] == y[j - 1] { 0 } else { 1 };
+                table[i][j] = (table[i - 1][j] + 1)
+                    .min(table[i][j - 1] + 1)
+                    .min(table[i - 1][j - 1] + cost);
+            }
+        }
+        table[x.len()][y.len()]
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(levenshtein_distance(b"", b""), 0);
+        assert_eq!(levenshtein_distance(b"abc", b""), 3);
+        assert_eq!(levenshtein_distance(b"", b"abc"), 3);
+        assert_eq!(levenshtein_distance(b"kitten", b"sitting"), 3);
+        assert_eq!(levenshtein_distance(b"flaw", b"lawn"), 2);
+
+        assert_eq!(Levenshtein("Saturday").distance(&Levenshtein("Sunday")), 3);
+        assert_eq!(Levenshtein("naïve").distance(&"naive"), 1);
+        assert_eq!(String::from("ab").distance(&Levenshtein("ba")), 2);
+        assert_eq!(Levenshtein(vec![1, 2, 3]).distance(&[1, 3][..]), 1);
+
+        assert_eq!(bounded_levenshtein_distance(b"kitten", b"sitting", 3), Some(3));
+        assert_eq!(bounded_levenshtein_distance(b"kitten", b"sitting", 2), None);
+        assert_eq!(bounded_levenshtein_distance(b"kitten", b"kitten", 0), Some(0));
+        assert_eq!(bounded_levenshtein_distance(b"a", b"a", -1), None);
+
+        assert_eq!(Levenshtein("kitten").distance_bounded(&"sitting", 3), Some(3));
+        assert_eq!(Levenshtein("kitten").distance_bounded(&Levenshtein("sitting"), 2), None);
+    }
+
+    #[test]
+    fn test_ascii() {
+        assert_eq!("kitten".ascii(), Some(&b"kitten"[..]));
+        assert_eq!(String::from("naïve").ascii(), None);
+        assert_eq!(b"kitten".ascii(), None);
+
+        assert_eq!(Levenshtein("café").distance(&"cafe"), 1);
+        assert_eq!(Levenshtein("cafe").distance(&Levenshtein("café")), 1);
+        assert_eq!(Levenshtein("café").distance_bounded(&"cafés", 0), None);
+        assert_eq!(Levenshtein("cafe").distance_bounded(&"cafes", 1), Some(1));
+    }
+
+    #[test]
+    fn test_random_distances() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let x: Vec<u8> = (0..rng.gen_range(0, 12)).map(|_| rng.gen_range(0, 3)).collect();
+            let y: Vec<u8> = (0..rng.gen_range(0, 12)).map(|_| rng.gen_range(0, 3)).collect();
+
+            let distance = naive_distance(&x, &y);
+            assert_eq!(levenshtein_distance(&x, &y) as usize, distance);
+
+            for bound in 0..12 {
+                let expected = Some(distance as i32).filter(|&d| d <= bound);
+                assert_eq!(bounded_levenshtein_distance(&x, &y, bound), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_lookup() {
+        let words = [
+            "apple", "apply", "ample", "maple", "angle", "ankle", "uncle", "apples", "applet",
+        ];
+        let words: Vec<_> = words.iter().copied().map(Levenshtein).collect();
+
+        let exhaustive = ExhaustiveSearch::from_iter(words.clone());
+        let vp = VpTree::from_iter(words.clone());
+        let bk = BkTree::from_iter(words);
+
+        for target in &["aple", "angel", "uncles", "xyz"] {
+            let target = Levenshtein(*target);
+            let distances = |neighbors: Vec<Neighbor<_, i32>>| -> Vec<_> {
+                neighbors.iter().map(|n| n.distance).collect()
+            };
+
+            let expected = distances(exhaustive.k_nearest(&target, 3));
+            assert_eq!(distances(vp.k_nearest(&target, 3)), expected);
+            assert_eq!(distances(bk.k_nearest(&target, 3)), expected);
+        }
+    }
+}
@@ -6,6 +6,9 @@ use crate::lp::Minkowski;
 
 use num_traits::zero;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 
@@ -16,6 +19,8 @@ use std::convert::TryFrom;
 /// [coordinate space]: Coordinates
 /// [Euclidean distance]: euclidean_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[repr(transparent)]
 pub struct Euclidean<T>(pub T);
 
 impl<T> Euclidean<T> {
@@ -73,6 +78,36 @@ where
     EuclideanDistance::from_squared(sum)
 }
 
+/// Compute the [Euclidean distance] between two points, if it is at most `bound`.
+///
+/// The sum of squares is checked against the bound as it goes, so points that are far apart can
+/// be rejected without looking at all of their coordinates.
+///
+/// [Euclidean distance]: https://en.wikipedia.org/wiki/Euclidean_distance
+pub fn bounded_euclidean_distance<T, U>(
+    x: T,
+    y: U,
+    bound: EuclideanDistance<T::Value>,
+) -> Option<EuclideanDistance<T::Value>>
+where
+    T: Coordinates,
+    U: Coordinates<Value = T::Value>,
+{
+    debug_assert!(x.dims() == y.dims());
+
+    let bound = bound.squared_value();
+    let mut sum = zero();
+    for i in 0..x.dims() {
+        let diff = x.coord(i) - y.coord(i);
+        sum += diff * diff;
+        if sum > bound {
+            return None;
+        }
+    }
+
+    Some(EuclideanDistance::from_squared(sum)).filter(|_| sum <= bound)
+}
+
 /// The Euclidean distance function.
 impl<T> Proximity for Euclidean<T>
 where
@@ -84,6 +119,10 @@ where
     fn distance(&self, other: &Self) -> Self::Distance {
         euclidean_distance(self, other)
     }
+
+    fn distance_bounded(&self, other: &Self, bound: Self::Distance) -> Option<Self::Distance> {
+        bounded_euclidean_distance(self, other, bound)
+    }
 }
 
 impl<T> Proximity<T> for Euclidean<T>
@@ -96,6 +135,10 @@ where
     fn distance(&self, other: &T) -> Self::Distance {
         euclidean_distance(self, other)
     }
+
+    fn distance_bounded(&self, other: &T, bound: Self::Distance) -> Option<Self::Distance> {
+        bounded_euclidean_distance(self, other, bound)
+    }
 }
 
 impl<T> Proximity<Euclidean<T>> for T
@@ -108,6 +151,14 @@ where
     fn distance(&self, other: &Euclidean<T>) -> Self::Distance {
         euclidean_distance(self, other)
     }
+
+    fn distance_bounded(
+        &self,
+        other: &Euclidean<T>,
+        bound: Self::Distance,
+    ) -> Option<Self::Distance> {
+        bounded_euclidean_distance(self, other, bound)
+    }
 }
 
 /// Euclidean distance is a metric.
@@ -164,6 +215,7 @@ where
 ///
 /// [Euclidean distance]: https://en.wikipedia.org/wiki/Euclidean_distance
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct EuclideanDistance<T>(T);
 
 impl<T: Value> EuclideanDistance<T> {
@@ -398,4 +450,18 @@ mod tests {
         assert!(5.0 < thirteen);
         assert!(-5.0 < thirteen);
     }
+
+    #[test]
+    fn test_bounded() {
+        let five = EuclideanDistance::from_squared(25);
+        assert_eq!(bounded_euclidean_distance([0, 0], [3, 4], five), Some(five));
+        assert_eq!(bounded_euclidean_distance([0, 0], [3, 5], five), None);
+        assert_eq!(bounded_euclidean_distance([0, 0], [6, 0], five), None);
+
+        let bound = EuclideanDistance::try_from(13.0).unwrap();
+        let thirteen = Euclidean([0.0, 0.0]).distance_bounded(&Euclidean([5.0, 12.0]), bound);
+        assert_eq!(thirteen, Some(bound));
+        assert_eq!(Euclidean([0.0, 0.0]).distance_bounded(&[5.0, 12.5], bound), None);
+        assert_eq!([0.0, 0.0].distance_bounded(&Euclidean([5.0, 12.5]), bound), None);
+    }
 }
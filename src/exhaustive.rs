@@ -3,10 +3,14 @@
 use crate::distance::Proximity;
 use crate::{ExactNeighbors, NearestNeighbors, Neighborhood};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use std::iter::FromIterator;
 
 /// A [`NearestNeighbors`] implementation that does exhaustive search.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct ExhaustiveSearch<T>(Vec<T>);
 
 impl<T> ExhaustiveSearch<T> {
@@ -20,6 +24,20 @@ impl<T> ExhaustiveSearch<T> {
         self.0.push(item);
     }
 
+    /// Remove an item from the index, returning it if it was present.
+    pub fn remove(&mut self, item: &T) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let i = self.0.iter().position(|x| x == item)?;
+        Some(self.0.swap_remove(i))
+    }
+
+    /// Retain only the items that satisfy a predicate.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.0.retain(f);
+    }
+
     /// Get the size of this index.
     pub fn len(&self) -> usize {
         self.0.len()
@@ -68,7 +86,10 @@ impl<K: Proximity<V>, V> NearestNeighbors<K, V> for ExhaustiveSearch<V> {
         N: Neighborhood<&'k K, &'v V>,
     {
         for e in &self.0 {
-            neighborhood.consider(e);
+            if neighborhood.is_exhausted() {
+                break;
+            }
+            neighborhood.consider_bounded(e);
         }
         neighborhood
     }
@@ -80,10 +101,36 @@ impl<K: Proximity<V>, V> ExactNeighbors<K, V> for ExhaustiveSearch<V> {}
 pub mod tests {
     use super::*;
 
-    use crate::tests::test_exact_neighbors;
+    use crate::tests::{test_exact_neighbors, test_remove, test_retain};
 
     #[test]
     fn test_exhaustive_index() {
         test_exact_neighbors(ExhaustiveSearch::from_iter);
     }
+
+    #[test]
+    fn test_exhaustive_remove() {
+        test_remove(ExhaustiveSearch::from_iter, ExhaustiveSearch::remove, ExhaustiveSearch::len);
+    }
+
+    #[test]
+    fn test_exhaustive_retain() {
+        test_retain(
+            ExhaustiveSearch::from_iter,
+            |index, f| index.retain(f),
+            ExhaustiveSearch::len,
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_exhaustive_serde() {
+        test_exact_neighbors(|points| {
+            let index = ExhaustiveSearch::from_iter(points);
+            let json = serde_json::to_string(&index).unwrap();
+            let copy: ExhaustiveSearch<_> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+            copy
+        });
+    }
 }
@@ -0,0 +1,461 @@
+//! A zero-copy binary format for flat trees.
+//!
+//! [`FlatVpTree`] and [`FlatKdTree`] keep their nodes in contiguous arrays, so they can be written
+//! out once and then searched directly from a byte buffer, such as a memory-mapped file, without
+//! deserializing anything.  Trees are written with [`FlatVpTree::write_to()`] and
+//! [`FlatKdTree::write_to()`], and read back with [`FlatVpTreeRef`] and [`FlatKdTreeRef`]:
+//!
+//!     use acap::euclid::Euclidean;
+//!     use acap::vp::{FlatVpTree, FlatVpTreeRef};
+//!     use acap::NearestNeighbors;
+//!
+//!     // Items with a dynamic number of coordinates are stored as fixed-width rows...
+//!     let tree = FlatVpTree::balanced(vec![
+//!         Euclidean(vec![3.0, 4.0]),
+//!         Euclidean(vec![5.0, 12.0]),
+//!         Euclidean(vec![8.0, 15.0]),
+//!         Euclidean(vec![7.0, 24.0]),
+//!     ]);
+//!
+//!     let mut bytes = Vec::new();
+//!     tree.write_to(&mut bytes)?;
+//!     # let words: Vec<u64> = bytes.chunks(8).map(|c| {
+//!     #     let mut word = [0; 8];
+//!     #     word[..c.len()].copy_from_slice(c);
+//!     #     u64::from_ne_bytes(word)
+//!     # }).collect();
+//!     # let bytes = unsafe { words.align_to::<u8>().1 };
+//!
+//!     // ...which are read back as arrays
+//!     let tree = FlatVpTreeRef::<Euclidean<[f64; 2]>>::from_bytes(&bytes)?;
+//!     let nearest = tree.nearest(&Euclidean([7.0, 7.0])).unwrap();
+//!     assert_eq!(nearest.item, &Euclidean([3.0, 4.0]));
+//!     # Ok::<(), Box<dyn std::error::Error>>(())
+//!
+//! Opening a tree only reads its header, so it takes constant time no matter how large the tree is.
+//! The buffer must be aligned for the stored types, which memory maps always are.  Since nothing
+//! else is checked up front, searching a corrupted tree may panic, but it cannot cause undefined
+//! behavior.
+//!
+//! # Layout
+//!
+//! All numbers are little-endian.  Every file starts with a 32-byte header:
+//!
+//! | Offset | Size | Contents                                                 |
+//! |--------|------|----------------------------------------------------------|
+//! | 0      | 8    | The magic bytes `b"ACAPFLAT"`                            |
+//! | 8      | 4    | The format version, currently [`VERSION`]                |
+//! | 12     | 1    | The kind of tree: 1 for VP trees, 2 for k-d trees        |
+//! | 13     | 1    | The [tag] of the coordinate type                         |
+//! | 14     | 1    | The [tag] of the radius type for VP trees, otherwise 0   |
+//! | 15     | 1    | Reserved, always 0                                       |
+//! | 16     | 8    | The number of coordinates in each item                   |
+//! | 24     | 8    | The number of items                                      |
+//!
+//! The header is followed by these sections, in order, each padded to a multiple of 8 bytes:
+//!
+//! * Every item, as a fixed-width row of coordinates.
+//! * For VP trees, the radius of every node.
+//! * The size of the inside (VP) or left (k-d) subtree of every node, as a `u64`.
+//!
+//! The nodes are in the same order as in the flat tree that was written, with each node followed
+//! by its inside (left) subtree and then its outside (right) subtree.
+//!
+//! [`FlatVpTree`]: crate::vp::FlatVpTree
+//! [`FlatVpTree::write_to()`]: crate::vp::FlatVpTree::write_to
+//! [`FlatVpTreeRef`]: crate::vp::FlatVpTreeRef
+//! [`FlatKdTree`]: crate::kd::FlatKdTree
+//! [`FlatKdTree::write_to()`]: crate::kd::FlatKdTree::write_to
+//! [`FlatKdTreeRef`]: crate::kd::FlatKdTreeRef
+//! [tag]: Scalar::TAG
+
+use crate::chebyshev::Chebyshev;
+use crate::coords::Coordinates;
+use crate::euclid::Euclidean;
+use crate::taxi::Taxicab;
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Write};
+use std::mem;
+
+/// The magic bytes at the start of every file.
+const MAGIC: [u8; 8] = *b"ACAPFLAT";
+
+/// The current version of the format.
+pub const VERSION: u32 = 1;
+
+/// The size of the header.
+const HEADER_LEN: usize = 32;
+
+/// The alignment of each section.
+const ALIGN: usize = 8;
+
+/// Round an offset up to the next section boundary.
+fn align(offset: usize) -> usize {
+    (offset + ALIGN - 1) & !(ALIGN - 1)
+}
+
+/// The kinds of tree that can be stored.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Kind {
+    Vp = 1,
+    Kd = 2,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Plain old data that can be read directly out of a byte buffer.
+///
+/// # Safety
+///
+/// Implementations must have exactly the layout of `[Self::Scalar; Self::DIMS]`, without any
+/// padding, and every bit pattern must be a valid value.
+pub unsafe trait Pod: Copy {
+    /// The type of each coordinate.
+    type Scalar: Scalar;
+
+    /// The number of coordinates.
+    const DIMS: usize;
+}
+
+/// A primitive number that can be stored in the flat format.
+pub trait Scalar: Pod + sealed::Sealed {
+    /// The tag that identifies this type in a header.
+    const TAG: u8;
+
+    /// Write this number in little-endian byte order.
+    fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()>;
+}
+
+/// Implement [`Scalar`] for a primitive type.
+macro_rules! scalar {
+    ($t:ty, $tag:expr) => {
+        impl sealed::Sealed for $t {}
+
+        unsafe impl Pod for $t {
+            type Scalar = $t;
+            const DIMS: usize = 1;
+        }
+
+        impl Scalar for $t {
+            const TAG: u8 = $tag;
+
+            fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+        }
+    };
+}
+
+scalar!(i8, 1);
+scalar!(i16, 2);
+scalar!(i32, 3);
+scalar!(i64, 4);
+scalar!(u8, 5);
+scalar!(u16, 6);
+scalar!(u32, 7);
+scalar!(u64, 8);
+scalar!(f32, 9);
+scalar!(f64, 10);
+
+/// Arrays of plain old data are plain old data.
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {
+    type Scalar = T::Scalar;
+    const DIMS: usize = T::DIMS * N;
+}
+
+/// `Euclidean` is `#[repr(transparent)]`.
+unsafe impl<T: Pod> Pod for Euclidean<T> {
+    type Scalar = T::Scalar;
+    const DIMS: usize = T::DIMS;
+}
+
+/// `Taxicab` is `#[repr(transparent)]`.
+unsafe impl<T: Pod> Pod for Taxicab<T> {
+    type Scalar = T::Scalar;
+    const DIMS: usize = T::DIMS;
+}
+
+/// `Chebyshev` is `#[repr(transparent)]`.
+unsafe impl<T: Pod> Pod for Chebyshev<T> {
+    type Scalar = T::Scalar;
+    const DIMS: usize = T::DIMS;
+}
+
+/// An error encountered while opening a flat tree.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FormatError {
+    /// The buffer doesn't start with the magic bytes.
+    BadMagic,
+    /// The buffer has an unsupported format version.
+    UnsupportedVersion(u32),
+    /// The buffer holds a different kind of tree.
+    WrongKind,
+    /// The buffer holds items or radii of different types.
+    TypeMismatch,
+    /// The buffer is too short.
+    Truncated,
+    /// The buffer isn't aligned for the stored types.
+    Misaligned,
+    /// This platform is big-endian, so the data can't be used in place.
+    BigEndian,
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not a flat tree"),
+            Self::UnsupportedVersion(v) => write!(f, "Unsupported flat tree version {}", v),
+            Self::WrongKind => write!(f, "Wrong kind of flat tree"),
+            Self::TypeMismatch => write!(f, "Flat tree has the wrong item or radius type"),
+            Self::Truncated => write!(f, "Flat tree is truncated"),
+            Self::Misaligned => write!(f, "Flat tree is misaligned"),
+            Self::BigEndian => write!(f, "Flat trees can't be used in place on big-endian targets"),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Writes a flat tree, section by section.
+#[derive(Debug)]
+pub(crate) struct Writer<W> {
+    /// The underlying writer.
+    writer: W,
+    /// The number of bytes written so far.
+    offset: usize,
+    /// The number of coordinates in each item.
+    dims: usize,
+}
+
+impl<W: Write> Writer<W> {
+    /// Write the header of a flat tree.
+    ///
+    /// `coords` and `radii` are the [tags](Scalar::TAG) of the coordinate and radius types, and
+    /// `dims` is the number of coordinates in each of the `len` items.
+    pub(crate) fn new(
+        writer: W,
+        kind: Kind,
+        coords: u8,
+        radii: u8,
+        dims: usize,
+        len: usize,
+    ) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.push(kind as u8);
+        header.push(coords);
+        header.push(radii);
+        header.push(0);
+        header.extend_from_slice(&(dims as u64).to_le_bytes());
+        header.extend_from_slice(&(len as u64).to_le_bytes());
+
+        let mut writer = Self {
+            writer,
+            offset: 0,
+            dims,
+        };
+        writer.write_bytes(&header)?;
+        Ok(writer)
+    }
+
+    /// Write some raw bytes.
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len();
+        Ok(())
+    }
+
+    /// Write a single number.
+    pub(crate) fn write<S: Scalar>(&mut self, value: S) -> io::Result<()> {
+        value.write_le(&mut self.writer)?;
+        self.offset += mem::size_of::<S>();
+        Ok(())
+    }
+
+    /// Write the size of a subtree.
+    pub(crate) fn write_len(&mut self, len: usize) -> io::Result<()> {
+        self.write(len as u64)
+    }
+
+    /// Write an item as a row of coordinates.
+    pub(crate) fn write_item<T>(&mut self, item: &T) -> io::Result<()>
+    where
+        T: ?Sized + Coordinates,
+        T::Value: Scalar,
+    {
+        if item.dims() != self.dims {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Items have different numbers of coordinates",
+            ));
+        }
+
+        for i in 0..self.dims {
+            self.write(item.coord(i))?;
+        }
+        Ok(())
+    }
+
+    /// Pad the current section out to a boundary.
+    pub(crate) fn end_section(&mut self) -> io::Result<()> {
+        let padding = [0; ALIGN];
+        let len = align(self.offset) - self.offset;
+        self.write_bytes(&padding[..len])
+    }
+}
+
+/// Reads a flat tree out of a byte buffer, section by section.
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    /// The whole buffer.
+    bytes: &'a [u8],
+    /// The offset of the next section.
+    offset: usize,
+    /// The number of items.
+    len: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Check the header of a flat tree with items of type `T`.
+    ///
+    /// `radius` is the tag of the radius type, or 0 for trees without radii.
+    pub(crate) fn new<T>(bytes: &'a [u8], kind: Kind, radius: u8) -> Result<Self, FormatError>
+    where
+        T: Pod,
+    {
+        if cfg!(target_endian = "big") {
+            return Err(FormatError::BigEndian);
+        }
+
+        let header = bytes.get(..HEADER_LEN).ok_or(FormatError::Truncated)?;
+        if header[..8] != MAGIC {
+            return Err(FormatError::BadMagic);
+        }
+
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+
+        if header[12] != kind as u8 {
+            return Err(FormatError::WrongKind);
+        }
+
+        let dims = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        let len = u64::from_le_bytes(header[24..32].try_into().unwrap());
+        let len = len.try_into().map_err(|_| FormatError::Truncated)?;
+        if header[13] != T::Scalar::TAG || header[14] != radius {
+            return Err(FormatError::TypeMismatch);
+        }
+        if len > 0 && dims != T::DIMS as u64 {
+            return Err(FormatError::TypeMismatch);
+        }
+
+        Ok(Self {
+            bytes,
+            offset: HEADER_LEN,
+            len,
+        })
+    }
+
+    /// Read the next section, which has one value per item.
+    pub(crate) fn section<U: Pod>(&mut self) -> Result<&'a [U], FormatError> {
+        let size = self
+            .len
+            .checked_mul(mem::size_of::<U>())
+            .ok_or(FormatError::Truncated)?;
+        let end = self
+            .offset
+            .checked_add(size)
+            .ok_or(FormatError::Truncated)?;
+        let bytes = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or(FormatError::Truncated)?;
+        self.offset = align(end);
+
+        // Safety: any bytes are valid Pod values
+        let (prefix, values, suffix) = unsafe { bytes.align_to::<U>() };
+        if prefix.is_empty() && suffix.is_empty() && values.len() == self.len {
+            Ok(values)
+        } else {
+            Err(FormatError::Misaligned)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::vp::{FlatVpTree, FlatVpTreeRef};
+
+    /// Copy some bytes into a buffer that is aligned for any scalar type.
+    pub fn aligned(bytes: &[u8]) -> &'static [u8] {
+        let words: Vec<u64> = bytes
+            .chunks(8)
+            .map(|chunk| {
+                let mut word = [0; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u64::from_ne_bytes(word)
+            })
+            .collect();
+
+        let words = Box::leak(words.into_boxed_slice());
+        // Safety: u8 has no invalid bit patterns
+        let (_, words, _) = unsafe { words.align_to::<u8>() };
+        &words[..bytes.len()]
+    }
+
+    #[test]
+    fn test_format_errors() {
+        type Point = Euclidean<[f32; 2]>;
+
+        let tree = FlatVpTree::balanced(vec![Euclidean([1.0f32, 2.0]), Euclidean([3.0, 4.0])]);
+        let mut bytes = Vec::new();
+        tree.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len() % ALIGN, 0);
+
+        let open = |bytes: &[u8]| FlatVpTreeRef::<Point>::from_bytes(aligned(bytes)).err();
+        assert_eq!(open(&bytes), None);
+        assert_eq!(open(&bytes[..HEADER_LEN - 1]), Some(FormatError::Truncated));
+        assert_eq!(open(&bytes[..bytes.len() - 1]), Some(FormatError::Truncated));
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(open(&bad), Some(FormatError::BadMagic));
+
+        let mut bad = bytes.clone();
+        bad[8] = 2;
+        assert_eq!(open(&bad), Some(FormatError::UnsupportedVersion(2)));
+
+        let mut bad = bytes.clone();
+        bad[12] = Kind::Kd as u8;
+        assert_eq!(open(&bad), Some(FormatError::WrongKind));
+
+        let bytes = aligned(&bytes);
+        assert!(FlatVpTreeRef::<Point>::from_bytes(bytes).is_ok());
+        assert_eq!(
+            FlatVpTreeRef::<Euclidean<[f64; 2]>>::from_bytes(bytes).err(),
+            Some(FormatError::TypeMismatch),
+        );
+        assert_eq!(
+            FlatVpTreeRef::<Euclidean<[f32; 3]>>::from_bytes(bytes).err(),
+            Some(FormatError::TypeMismatch),
+        );
+
+        let mut copy = vec![0; bytes.len() + 1];
+        copy[1..].copy_from_slice(bytes);
+        let copy = aligned(&copy);
+        assert_eq!(
+            FlatVpTreeRef::<Point>::from_bytes(&copy[1..]).err(),
+            Some(FormatError::Misaligned),
+        );
+    }
+}
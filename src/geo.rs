@@ -0,0 +1,487 @@
+//! [Geographic coordinates](https://en.wikipedia.org/wiki/Geographic_coordinate_system).
+
+use crate::distance::{Distance, Metric, Proximity, Value};
+use crate::euclid::NegativeDistanceError;
+
+use num_traits::real::Real;
+use num_traits::{one, zero};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+
+/// The mean radius of the Earth, in meters.
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The semi-major axis of the [WGS 84] ellipsoid, in meters.
+///
+/// [WGS 84]: https://en.wikipedia.org/wiki/World_Geodetic_System
+pub const WGS84_A: f64 = 6_378_137.0;
+
+/// The flattening of the [WGS 84] ellipsoid.
+///
+/// [WGS 84]: https://en.wikipedia.org/wiki/World_Geodetic_System
+pub const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Convert a constant to the given floating-point type.
+fn constant<T: Real>(x: f64) -> T {
+    T::from(x).unwrap()
+}
+
+/// A point on the surface of the Earth, given by its latitude and longitude in degrees.
+///
+/// Points are compared by the [haversine] (great-circle) distance, in meters.  For more accuracy,
+/// wrap them in [`Vincenty`] instead.
+///
+///     use acap::distance::{Distance, Proximity};
+///     use acap::geo::LatLon;
+///
+///     let paris: LatLon = LatLon::new(48.8566, 2.3522);
+///     let london = LatLon::new(51.5074, -0.1278);
+///     let distance = paris.distance(&london);
+///     assert!(343_000.0 < distance && distance < 344_000.0);
+///     assert!((distance.value() - 343_556.5).abs() < 1.0);
+///
+/// [haversine]: haversine_distance
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct LatLon<T = f64> {
+    /// The latitude, in degrees north of the equator.
+    pub lat: T,
+    /// The longitude, in degrees east of the prime meridian.
+    pub lon: T,
+}
+
+impl<T> LatLon<T> {
+    /// Create a new point from its latitude and longitude, in degrees.
+    pub fn new(lat: T, lon: T) -> Self {
+        Self { lat, lon }
+    }
+}
+
+/// Compute the [haversine] (great-circle) distance between two points on the Earth.
+///
+/// The Earth is treated as a sphere with radius [`EARTH_RADIUS`], which gives errors of up to
+/// about 0.5%.
+///
+/// ```math
+/// \begin{aligned}
+/// \mathrm{hav}(\theta) &= \mathrm{hav}(\Delta\varphi) + \cos\varphi_1 \cos\varphi_2 \, \mathrm{hav}(\Delta\lambda) \\
+/// \mathrm{haversine\_distance}(x, y) &= R \, \theta
+/// \end{aligned}
+/// ```
+///
+/// [haversine]: https://en.wikipedia.org/wiki/Haversine_formula
+pub fn haversine_distance<T: Real>(x: LatLon<T>, y: LatLon<T>) -> HaversineDistance<T> {
+    let two = constant::<T>(2.0);
+    let (lat1, lat2) = (x.lat.to_radians(), y.lat.to_radians());
+    let dlat = (lat2 - lat1) / two;
+    let dlon = (y.lon - x.lon).to_radians() / two;
+
+    let hav = dlat.sin().powi(2) + lat1.cos() * lat2.cos() * dlon.sin().powi(2);
+    HaversineDistance(hav.max(zero()).min(one()))
+}
+
+/// The haversine distance function.
+impl<T> Proximity for LatLon<T>
+where
+    T: Real,
+    HaversineDistance<T>: Distance,
+{
+    type Distance = HaversineDistance<T>;
+
+    fn distance(&self, other: &Self) -> Self::Distance {
+        haversine_distance(*self, *other)
+    }
+}
+
+/// Haversine distance is a metric.
+impl<T> Metric for LatLon<T>
+where
+    T: Real,
+    HaversineDistance<T>: Distance,
+{}
+
+/// A [haversine] distance.
+///
+/// This type stores the [haversine] of the central angle between two points, rather than the
+/// distance itself.  Comparisons still behave correctly, because the haversine increases with the
+/// angle, and expensive inverse trigonometric functions are avoided until the `value()` in meters
+/// is needed.
+///
+///     # use acap::distance::Distance;
+///     # use acap::geo::HaversineDistance;
+///     # use std::convert::TryFrom;
+///     let a = HaversineDistance::try_from(1000.0f64).unwrap();
+///     let b = HaversineDistance::try_from(2000.0).unwrap();
+///     assert!(a < b && a < 1500.0 && 1500.0 < b);
+///     assert!((b.value() - 2000.0).abs() < 1e-6);
+///
+/// [haversine]: https://en.wikipedia.org/wiki/Haversine_formula
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct HaversineDistance<T>(T);
+
+impl<T: Value> HaversineDistance<T> {
+    /// Creates a `HaversineDistance` from the haversine of a central angle.
+    pub fn from_haversine(value: T) -> Self {
+        debug_assert!(!value.is_negative() && value <= one());
+        Self(value)
+    }
+
+    /// Get the haversine of the central angle.
+    pub fn haversine_value(self) -> T {
+        self.0
+    }
+}
+
+/// Implement HaversineDistance for a floating-point type.
+macro_rules! float_haversine {
+    ($f:ty) => {
+        impl TryFrom<$f> for HaversineDistance<$f> {
+            type Error = NegativeDistanceError;
+
+            /// Convert a distance in meters.  Distances beyond the antipode are clamped to it.
+            #[inline]
+            fn try_from(value: $f) -> Result<Self, Self::Error> {
+                if value >= 0.0 {
+                    let angle = value / EARTH_RADIUS as $f;
+                    if angle < PI as $f {
+                        Ok(Self((angle / 2.0).sin().powi(2)))
+                    } else {
+                        Ok(Self(1.0))
+                    }
+                } else {
+                    Err(NegativeDistanceError)
+                }
+            }
+        }
+
+        impl From<HaversineDistance<$f>> for $f {
+            #[inline]
+            fn from(value: HaversineDistance<$f>) -> $f {
+                2.0 * EARTH_RADIUS as $f * value.0.sqrt().asin()
+            }
+        }
+
+        impl PartialOrd<$f> for HaversineDistance<$f> {
+            #[inline]
+            fn partial_cmp(&self, other: &$f) -> Option<Ordering> {
+                if *other > PI as $f * EARTH_RADIUS as $f {
+                    // Nothing is farther than the antipode
+                    Some(Ordering::Less)
+                } else if let Ok(rhs) = Self::try_from(*other) {
+                    self.partial_cmp(&rhs)
+                } else {
+                    Some(Ordering::Greater)
+                }
+            }
+        }
+
+        impl PartialOrd<HaversineDistance<$f>> for $f {
+            #[inline]
+            fn partial_cmp(&self, other: &HaversineDistance<$f>) -> Option<Ordering> {
+                other.partial_cmp(self).map(Ordering::reverse)
+            }
+        }
+
+        impl PartialEq<$f> for HaversineDistance<$f> {
+            #[inline]
+            fn eq(&self, other: &$f) -> bool {
+                self.partial_cmp(other) == Some(Ordering::Equal)
+            }
+        }
+
+        impl PartialEq<HaversineDistance<$f>> for $f {
+            #[inline]
+            fn eq(&self, other: &HaversineDistance<$f>) -> bool {
+                self.partial_cmp(other) == Some(Ordering::Equal)
+            }
+        }
+
+        impl Distance for HaversineDistance<$f> {
+            type Value = $f;
+        }
+    };
+}
+
+float_haversine!(f32);
+float_haversine!(f64);
+
+/// A point on the surface of the Earth, compared by [Vincenty's formulae].
+///
+/// This wrapper measures distances along the [WGS 84] ellipsoid, which is accurate to within a
+/// millimeter or so, but several times slower than the spherical [`haversine_distance()`].
+///
+/// Since [`vincenty_distance()`] falls back to the haversine distance for some nearly antipodal
+/// points, it is only approximately a [metric](Metric), so it does not implement [`Metric`].
+/// That means it only works with indexes that don't rely on the triangle inequality, like
+/// [`ExhaustiveSearch`].  Use [`LatLon`] directly with metric indexes like [`VpTree`].
+///
+///     use acap::distance::Proximity;
+///     use acap::geo::{LatLon, Vincenty};
+///
+///     let paris = Vincenty(LatLon::new(48.8566, 2.3522));
+///     let london = Vincenty(LatLon::new(51.5074, -0.1278));
+///     let distance = paris.distance(&london);
+///     assert!(343_000.0 < distance && distance < 345_000.0);
+///
+/// [Vincenty's formulae]: vincenty_distance
+/// [`ExhaustiveSearch`]: crate::exhaustive::ExhaustiveSearch
+/// [`VpTree`]: crate::vp::VpTree
+/// [WGS 84]: https://en.wikipedia.org/wiki/World_Geodetic_System
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct Vincenty<T = f64>(pub LatLon<T>);
+
+impl<T> Vincenty<T> {
+    /// Wrap a point.
+    pub fn new(point: LatLon<T>) -> Self {
+        Self(point)
+    }
+
+    /// Unwrap a point.
+    pub fn inner(&self) -> &LatLon<T> {
+        &self.0
+    }
+
+    /// Unwrap a point.
+    pub fn into_inner(self) -> LatLon<T> {
+        self.0
+    }
+}
+
+/// Compute the distance in meters between two points on the [WGS 84] ellipsoid, using the inverse
+/// [Vincenty's formulae].
+///
+/// The iteration fails to converge for some nearly antipodal points.  In that case, this falls
+/// back to the [`haversine_distance()`], which is accurate to within about 0.5%.
+///
+/// [WGS 84]: https://en.wikipedia.org/wiki/World_Geodetic_System
+/// [Vincenty's formulae]: https://en.wikipedia.org/wiki/Vincenty%27s_formulae
+pub fn vincenty_distance<T: Real>(x: LatLon<T>, y: LatLon<T>) -> T
+where
+    HaversineDistance<T>: Distance<Value = T>,
+{
+    let c = constant::<T>;
+    let (a, f) = (c(WGS84_A), c(WGS84_F));
+    let b = (c(1.0) - f) * a;
+
+    let (sin_u1, cos_u1) = ((c(1.0) - f) * x.lat.to_radians().tan()).atan().sin_cos();
+    let (sin_u2, cos_u2) = ((c(1.0) - f) * y.lat.to_radians().tan()).atan().sin_cos();
+    let l = (y.lon - x.lon).to_radians();
+    let tolerance = c(1e-12).max(c(8.0) * T::epsilon());
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == zero() {
+            // Coincident points
+            return zero();
+        }
+
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos2_alpha = c(1.0) - sin_alpha.powi(2);
+        let cos_2sigma_m = if cos2_alpha == zero() {
+            // Equatorial line
+            zero()
+        } else {
+            cos_sigma - c(2.0) * sin_u1 * sin_u2 / cos2_alpha
+        };
+
+        let cc = f / c(16.0) * cos2_alpha * (c(4.0) + f * (c(4.0) - c(3.0) * cos2_alpha));
+        let prev = lambda;
+        let correction = cos_2sigma_m + cc * cos_sigma * (c(2.0) * cos_2sigma_m.powi(2) - c(1.0));
+        lambda = l + (c(1.0) - cc) * f * sin_alpha * (sigma + cc * sin_sigma * correction);
+
+        if (lambda - prev).abs() <= tolerance {
+            let u2 = cos2_alpha * (a * a - b * b) / (b * b);
+            let big_a = c(1.0)
+                + u2 / c(16384.0)
+                    * (c(4096.0) + u2 * (c(-768.0) + u2 * (c(320.0) - c(175.0) * u2)));
+            let big_b =
+                u2 / c(1024.0) * (c(256.0) + u2 * (c(-128.0) + u2 * (c(74.0) - c(47.0) * u2)));
+            let delta_sigma = big_b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + big_b / c(4.0)
+                        * (cos_sigma * (c(2.0) * cos_2sigma_m.powi(2) - c(1.0))
+                            - big_b / c(6.0)
+                                * cos_2sigma_m
+                                * (c(4.0) * sin_sigma.powi(2) - c(3.0))
+                                * (c(4.0) * cos_2sigma_m.powi(2) - c(3.0))));
+
+            return b * big_a * (sigma - delta_sigma);
+        }
+    }
+
+    haversine_distance(x, y).value()
+}
+
+/// The Vincenty distance function.
+impl<T> Proximity for Vincenty<T>
+where
+    T: Real + Value,
+    HaversineDistance<T>: Distance<Value = T>,
+{
+    type Distance = T;
+
+    fn distance(&self, other: &Self) -> Self::Distance {
+        vincenty_distance(self.0, other.0)
+    }
+}
+
+impl<T> Proximity<LatLon<T>> for Vincenty<T>
+where
+    T: Real + Value,
+    HaversineDistance<T>: Distance<Value = T>,
+{
+    type Distance = T;
+
+    fn distance(&self, other: &LatLon<T>) -> Self::Distance {
+        vincenty_distance(self.0, *other)
+    }
+}
+
+impl<T> Proximity<Vincenty<T>> for LatLon<T>
+where
+    T: Real + Value,
+    HaversineDistance<T>: Distance<Value = T>,
+{
+    type Distance = T;
+
+    fn distance(&self, other: &Vincenty<T>) -> Self::Distance {
+        vincenty_distance(*self, other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::vp::VpTree;
+    use crate::NearestNeighbors;
+
+    use rand::prelude::*;
+
+    use std::iter::FromIterator;
+
+    /// Convert degrees, minutes, and seconds to degrees.
+    fn dms(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        degrees.signum() * (degrees.abs() + minutes / 60.0 + seconds / 3600.0)
+    }
+
+    #[test]
+    fn test_haversine() {
+        let paris: LatLon = LatLon::new(48.8566, 2.3522);
+        let london = LatLon::new(51.5074, -0.1278);
+        let distance = paris.distance(&london);
+        assert!((distance.value() - 343_556.535).abs() < 0.01);
+        assert_eq!(distance, london.distance(&paris));
+        assert_eq!(paris.distance(&paris), 0.0);
+
+        assert!(distance < 343_557.0);
+        assert!(343_556.0 < distance);
+        assert!(-1.0 < distance);
+        assert!(distance < 1e9);
+
+        let antipode = LatLon::new(-48.8566, -177.6478);
+        let distance = paris.distance(&antipode);
+        assert_eq!(distance.haversine_value(), 1.0);
+        assert!((distance.value() - PI * EARTH_RADIUS).abs() < 1e-6);
+        assert!(distance <= 1e9);
+        assert!(1e9 >= distance);
+
+        let zero = HaversineDistance::from_haversine(0.0);
+        let one = HaversineDistance::from_haversine(1.0);
+        assert_eq!(HaversineDistance::try_from(0.0).unwrap(), zero);
+        assert_eq!(HaversineDistance::try_from(1e9).unwrap(), one);
+        assert!(HaversineDistance::try_from(-1.0).is_err());
+    }
+
+    #[test]
+    fn test_vincenty() {
+        // From Vincenty's original paper, via Geoscience Australia
+        let flinders_peak = LatLon::new(dms(-37.0, 57.0, 3.7203), dms(144.0, 25.0, 29.5244));
+        let buninyong = LatLon::new(dms(-37.0, 39.0, 10.1561), dms(143.0, 55.0, 35.3839));
+        let (flinders_peak, buninyong) = (Vincenty(flinders_peak), Vincenty(buninyong));
+        let distance = flinders_peak.distance(&buninyong);
+        assert!((distance - 54_972.271).abs() < 0.001);
+        assert_eq!(flinders_peak.distance(&flinders_peak), 0.0);
+        assert!((buninyong.distance(flinders_peak.inner()) - distance).abs() < 1e-6);
+
+        let equator = Vincenty(LatLon::new(0.0, 0.0));
+        let distance = equator.distance(&LatLon::new(0.0, 1.0));
+        assert!((distance - 111_319.491).abs() < 0.001);
+
+        // Nearly antipodal points fall back to the haversine distance
+        let distance = equator.distance(&LatLon::new(0.5, 179.7));
+        assert!((distance - 19_950_277.343).abs() < 0.01);
+    }
+
+    fn random_point() -> LatLon {
+        let mut rng = thread_rng();
+        LatLon::new(rng.gen_range(-90.0, 90.0), rng.gen_range(-180.0, 180.0))
+    }
+
+    #[test]
+    fn test_store_locator() {
+        let stores: Vec<_> = (0..256).map(|_| random_point()).collect();
+        let exhaustive = ExhaustiveSearch::from_iter(stores.clone());
+        let vp = VpTree::from_iter(stores);
+
+        for _ in 0..100 {
+            let target = random_point();
+            assert_eq!(vp.k_nearest(&target, 3), exhaustive.k_nearest(&target, 3));
+            assert_eq!(
+                vp.k_nearest_within(&target, 3, 1_000_000.0),
+                exhaustive.k_nearest_within(&target, 3, 1_000_000.0),
+            );
+        }
+    }
+
+    #[test]
+    fn test_vincenty_locator() {
+        let stores: Vec<_> = (0..256).map(|_| Vincenty(random_point())).collect();
+        let exhaustive = ExhaustiveSearch::from_iter(stores.clone());
+
+        for _ in 0..100 {
+            let target = random_point();
+            let nearest = exhaustive.nearest(&target).unwrap();
+
+            let mut distances: Vec<_> = stores.iter().map(|s| target.distance(s)).collect();
+            distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(nearest.distance, distances[0]);
+
+            let k_nearest = exhaustive.k_nearest(&target, 3);
+            let k_distances: Vec<_> = k_nearest.iter().map(|n| n.distance).collect();
+            assert_eq!(k_distances, distances[..3]);
+        }
+    }
+
+    #[test]
+    fn test_antipodal_locator() {
+        let mut rng = thread_rng();
+        let mut near = |lat: f64, lon: f64| {
+            LatLon::new(lat + rng.gen_range(-1.0, 1.0), lon + rng.gen_range(-1.0, 1.0))
+        };
+
+        let stores: Vec<_> = (0..256).map(|_| near(0.0, 179.0)).collect();
+        let exhaustive = ExhaustiveSearch::from_iter(stores.clone());
+        let vp = VpTree::from_iter(stores);
+
+        for _ in 0..100 {
+            let target = near(0.0, 0.0);
+            assert_eq!(vp.k_nearest(&target, 3), exhaustive.k_nearest(&target, 3));
+        }
+    }
+}
@@ -4,12 +4,16 @@ use crate::distance::{Metric, Proximity};
 
 use num_traits::PrimInt;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// A point in Hamming space.
 ///
 /// This wrapper equips any integer with the [Hamming distance] metric.
 ///
 /// [Hamming distance]: hamming_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Hamming<T>(pub T);
 
 impl<T> Hamming<T> {
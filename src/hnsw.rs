@@ -0,0 +1,410 @@
+//! [Hierarchical navigable small world graphs](https://arxiv.org/abs/1603.09320).
+
+use crate::distance::Proximity;
+use crate::util::{Ordered, Rng};
+use crate::{NearestNeighbors, Neighborhood};
+
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::iter::{Extend, FromIterator};
+
+/// The default maximum number of links per node, per layer.
+const DEFAULT_M: usize = 16;
+
+/// The default size of the dynamic candidate list during construction.
+const DEFAULT_EF_CONSTRUCTION: usize = 200;
+
+/// The default size of the dynamic candidate list during search.
+const DEFAULT_EF_SEARCH: usize = 64;
+
+/// The seed for the random level generator.
+const SEED: u64 = 0x4853_4E57_4853_4E57;
+
+/// A node in an HNSW graph.
+#[derive(Debug)]
+struct HnswNode<T> {
+    /// The item stored in this node.
+    item: T,
+    /// The outgoing links from this node, for every layer it belongs to.
+    links: Vec<Vec<usize>>,
+}
+
+impl<T> HnswNode<T> {
+    /// Get the highest layer this node belongs to.
+    fn level(&self) -> usize {
+        self.links.len() - 1
+    }
+}
+
+/// A [hierarchical navigable small world] graph.
+///
+/// An HNSW graph is an approximate nearest neighbor index that works for any [`Proximity`]
+/// function.  Each item is inserted into a random number of layers, with exponentially fewer items
+/// in higher layers.  Searches greedily descend through the sparse upper layers, and finish with a
+/// beam search of the bottom layer.
+///
+/// The accuracy of the search can be tuned with these parameters:
+///
+/// * `M`: The maximum number of links per node in each layer (twice that in the bottom layer).
+/// * `ef_construction`: The number of candidate neighbors to consider when inserting an item.
+/// * `ef_search`: The number of candidate neighbors to consider when searching.
+///
+/// [hierarchical navigable small world]: https://arxiv.org/abs/1603.09320
+#[derive(Debug)]
+pub struct Hnsw<T> {
+    /// The nodes of the graph.
+    nodes: Vec<HnswNode<T>>,
+    /// The entry point into the top layer, if any.
+    entry: Option<usize>,
+    /// The maximum number of links per node in the upper layers.
+    m: usize,
+    /// The maximum number of links per node in the bottom layer.
+    m0: usize,
+    /// The size of the dynamic candidate list during construction.
+    ef_construction: usize,
+    /// The size of the dynamic candidate list during search.
+    ef_search: usize,
+    /// The normalization factor for the level distribution.
+    level_mult: f64,
+    /// The random level generator.
+    rng: Rng,
+}
+
+impl<T> Hnsw<T> {
+    /// Create an empty graph with the default parameters.
+    pub fn new() -> Self {
+        Self::with_params(DEFAULT_M, DEFAULT_EF_CONSTRUCTION)
+    }
+
+    /// Create an empty graph.
+    ///
+    /// * `m`: The maximum number of links per node in each layer.  Must be at least 2.
+    /// * `ef_construction`: The number of candidate neighbors to consider when inserting an item.
+    pub fn with_params(m: usize, ef_construction: usize) -> Self {
+        assert!(m >= 2, "M must be at least 2");
+
+        Self {
+            nodes: Vec::new(),
+            entry: None,
+            m,
+            m0: 2 * m,
+            ef_construction: ef_construction.max(1),
+            ef_search: DEFAULT_EF_SEARCH,
+            level_mult: 1.0 / (m as f64).ln(),
+            rng: Rng::new(SEED),
+        }
+    }
+
+    /// Get the maximum number of links per node in each layer.
+    pub fn m(&self) -> usize {
+        self.m
+    }
+
+    /// Get the number of candidate neighbors considered when inserting an item.
+    pub fn ef_construction(&self) -> usize {
+        self.ef_construction
+    }
+
+    /// Get the number of candidate neighbors considered when searching.
+    pub fn ef_search(&self) -> usize {
+        self.ef_search
+    }
+
+    /// Set the number of candidate neighbors considered when searching.
+    ///
+    /// Larger values give more accurate results, at the expense of slower searches.
+    pub fn set_ef_search(&mut self, ef_search: usize) {
+        self.ef_search = ef_search.max(1);
+    }
+
+    /// Get the size of this graph.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if this graph is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Pick a random level for a new node.
+    fn random_level(&mut self) -> usize {
+        // 1.0 - x is in (0, 1], avoiding ln(0)
+        let x = 1.0 - self.rng.next_f64();
+        (-x.ln() * self.level_mult) as usize
+    }
+
+    /// Search a single layer of the graph, starting from the given entry points.
+    ///
+    /// Returns up to `ef` nodes, sorted from nearest to farthest.  The search stops early if the
+    /// `distance` function returns `None`.
+    fn search_layer<D, F>(
+        &self,
+        entries: &[(D, usize)],
+        ef: usize,
+        layer: usize,
+        mut distance: F,
+    ) -> Vec<(D, usize)>
+    where
+        D: Copy + PartialOrd,
+        F: FnMut(usize) -> Option<D>,
+    {
+        let mut visited: HashSet<usize> = entries.iter().map(|&(_, i)| i).collect();
+
+        // A min-heap of nodes left to expand
+        let mut candidates: BinaryHeap<_> = entries
+            .iter()
+            .map(|&(d, i)| Reverse((Ordered::new(d), i)))
+            .collect();
+
+        // A max-heap of the nearest nodes found so far
+        let mut results: BinaryHeap<_> = entries
+            .iter()
+            .map(|&(d, i)| (Ordered::new(d), i))
+            .collect();
+        while results.len() > ef {
+            results.pop();
+        }
+
+        'search: while let Some(Reverse((dist, i))) = candidates.pop() {
+            if results.len() >= ef && dist > results.peek().unwrap().0 {
+                break;
+            }
+
+            for &j in &self.nodes[i].links[layer] {
+                if !visited.insert(j) {
+                    continue;
+                }
+
+                let dist = match distance(j) {
+                    Some(dist) => Ordered::new(dist),
+                    None => break 'search,
+                };
+                if results.len() < ef || dist < results.peek().unwrap().0 {
+                    candidates.push(Reverse((dist, j)));
+                    results.push((dist, j));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|(d, i)| (d.into_inner(), i))
+            .collect()
+    }
+}
+
+impl<T: Proximity> Hnsw<T> {
+    /// Push a new item into the graph.
+    pub fn push(&mut self, item: T) {
+        let index = self.nodes.len();
+        let level = self.random_level();
+        let mut links = vec![Vec::new(); level + 1];
+
+        let promote = if let Some(entry) = self.entry {
+            let top = self.nodes[entry].level();
+            let nodes = &self.nodes;
+            let distance = |i: usize| Some(item.distance(&nodes[i].item));
+
+            let mut entries = vec![(item.distance(&nodes[entry].item), entry)];
+
+            for layer in (level + 1..=top).rev() {
+                entries = self.search_layer(&entries, 1, layer, &distance);
+            }
+
+            for layer in (0..=level.min(top)).rev() {
+                let ef = self.ef_construction;
+                entries = self.search_layer(&entries, ef, layer, &distance);
+                links[layer] = self.select_neighbors(&entries, self.max_links(layer));
+            }
+
+            level > top
+        } else {
+            true
+        };
+
+        self.nodes.push(HnswNode { item, links });
+
+        for layer in 0..=level {
+            for i in 0..self.nodes[index].links[layer].len() {
+                let neighbor = self.nodes[index].links[layer][i];
+                self.connect(neighbor, index, layer);
+            }
+        }
+
+        if promote {
+            self.entry = Some(index);
+        }
+    }
+
+    /// Get the maximum number of links per node in a layer.
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.m0
+        } else {
+            self.m
+        }
+    }
+
+    /// Select up to `m` neighbors from a sorted list of candidates.
+    ///
+    /// This uses the heuristic from the HNSW paper, which prefers candidates that are closer to the
+    /// base item than to any already-selected neighbor.  This keeps links pointing in diverse
+    /// directions, which improves the connectivity of the graph.
+    fn select_neighbors(&self, candidates: &[(T::Distance, usize)], m: usize) -> Vec<usize> {
+        let mut selected = Vec::with_capacity(m);
+        let mut pruned = Vec::new();
+
+        for &(dist, i) in candidates {
+            if selected.len() >= m {
+                break;
+            }
+
+            let item = &self.nodes[i].item;
+            if selected.iter().all(|&j: &usize| dist < item.distance(&self.nodes[j].item)) {
+                selected.push(i);
+            } else {
+                pruned.push(i);
+            }
+        }
+
+        // Fill any remaining space with the nearest pruned candidates
+        for i in pruned {
+            if selected.len() >= m {
+                break;
+            }
+            selected.push(i);
+        }
+
+        selected
+    }
+
+    /// Add a link from one node to another, shrinking its neighbor list if necessary.
+    fn connect(&mut self, from: usize, to: usize, layer: usize) {
+        let max = self.max_links(layer);
+
+        let links = &mut self.nodes[from].links[layer];
+        links.push(to);
+        if links.len() <= max {
+            return;
+        }
+
+        let links = std::mem::take(links);
+        let base = &self.nodes[from].item;
+        let mut candidates: Vec<_> = links
+            .into_iter()
+            .map(|i| (base.distance(&self.nodes[i].item), i))
+            .collect();
+        candidates.sort_by_key(|&(dist, _)| Ordered::new(dist));
+
+        self.nodes[from].links[layer] = self.select_neighbors(&candidates, max);
+    }
+}
+
+impl<T> Default for Hnsw<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Proximity> Extend<T> for Hnsw<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Proximity> FromIterator<T> for Hnsw<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut graph = Self::new();
+        graph.extend(items);
+        graph
+    }
+}
+
+/// An iterator that moves values out of an HNSW graph.
+#[derive(Debug)]
+pub struct IntoIter<T>(std::vec::IntoIter<HnswNode<T>>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.next().map(|n| n.item)
+    }
+}
+
+impl<T> IntoIterator for Hnsw<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self.nodes.into_iter())
+    }
+}
+
+impl<K: Proximity<V>, V> NearestNeighbors<K, V> for Hnsw<V> {
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if let Some(entry) = self.entry {
+            // Nodes can be reached again in lower layers, but should only be considered once
+            let mut distances = HashMap::new();
+            let mut distance = |i: usize| match distances.entry(i) {
+                Entry::Occupied(entry) => Some(*entry.get()),
+                Entry::Vacant(_) if neighborhood.is_exhausted() => None,
+                Entry::Vacant(entry) => {
+                    let item = &self.nodes[i].item;
+                    Some(*entry.insert(neighborhood.consider(item)))
+                }
+            };
+
+            let mut entries = match distance(entry) {
+                Some(dist) => vec![(dist, entry)],
+                None => return neighborhood,
+            };
+
+            for layer in (1..=self.nodes[entry].level()).rev() {
+                entries = self.search_layer(&entries, 1, layer, &mut distance);
+            }
+
+            self.search_layer(&entries, self.ef_search, 0, &mut distance);
+        }
+        neighborhood
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tests::test_nearest_neighbors;
+
+    #[test]
+    fn test_hnsw() {
+        test_nearest_neighbors(|points| {
+            let mut graph = Hnsw::from_iter(points);
+            graph.set_ef_search(graph.len());
+            graph
+        });
+    }
+
+    #[test]
+    fn test_small_hnsw() {
+        test_nearest_neighbors(|points| {
+            let mut graph = Hnsw::with_params(4, 16);
+            graph.extend(points);
+            graph.set_ef_search(graph.len());
+            graph
+        });
+    }
+}
@@ -0,0 +1,387 @@
+//! [Inverted file indexes](https://en.wikipedia.org/wiki/Inverted_index), with a [k-means]
+//! coarse quantizer.
+//!
+//! [k-means]: https://en.wikipedia.org/wiki/K-means_clustering
+
+use crate::coords::Coordinates;
+use crate::distance::Proximity;
+use crate::euclid::euclidean_distance;
+use crate::util::{Ordered, Rng};
+use crate::{NearestNeighbors, Neighborhood};
+
+use num_traits::real::Real;
+use num_traits::{zero, NumCast, ToPrimitive};
+
+use std::iter::{Extend, FromIterator};
+
+/// The default number of cells to probe during a search.
+const DEFAULT_NPROBE: usize = 8;
+
+/// The maximum number of training points to sample for each centroid.
+const MAX_TRAINING_POINTS: usize = 256;
+
+/// The maximum number of k-means iterations.
+const ITERATIONS: usize = 16;
+
+/// The seed for the random training sample and initial centroids.
+const SEED: u64 = 0x4956_464B_4D45_414E;
+
+/// Compute the squared Euclidean distance between a centroid and a point.
+fn squared_distance<T>(centroid: &[T::Value], point: &T) -> T::Value
+where
+    T: ?Sized + Coordinates,
+{
+    euclidean_distance(centroid, point).squared_value()
+}
+
+/// Find the index of the nearest centroid to a point.
+fn nearest_centroid<T>(centroids: &[Vec<T::Value>], point: &T) -> usize
+where
+    T: ?Sized + Coordinates,
+{
+    centroids
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, c)| Ordered::new(squared_distance(c, point)))
+        .map(|(i, _)| i)
+        .unwrap()
+}
+
+/// Cluster some points into (at most) `k` cells with [k-means].
+///
+/// The centroids are seeded with [k-means++], then refined with Lloyd's algorithm on a random
+/// sample of the points.
+///
+/// [k-means]: https://en.wikipedia.org/wiki/K-means_clustering
+/// [k-means++]: https://en.wikipedia.org/wiki/K-means%2B%2B
+pub(crate) fn kmeans<T>(points: &[T], k: usize) -> Vec<Vec<T::Value>>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    let mut rng = Rng::new(SEED);
+
+    // Partial Fisher-Yates shuffle to pick the training sample
+    let mut indices: Vec<_> = (0..points.len()).collect();
+    let size = points.len().min(k.saturating_mul(MAX_TRAINING_POINTS));
+    for i in 0..size {
+        let j = i + rng.index(points.len() - i);
+        indices.swap(i, j);
+    }
+    let sample: Vec<_> = indices[..size].iter().map(|&i| &points[i]).collect();
+
+    let mut centroids = Vec::with_capacity(k);
+    centroids.push(sample[rng.index(size)].as_vec());
+
+    // k-means++: pick each new centroid with probability proportional to its squared distance
+    let mut nearest: Vec<_> = sample
+        .iter()
+        .map(|x| squared_distance(&centroids[0], *x))
+        .collect();
+    while centroids.len() < k {
+        let total: f64 = nearest.iter().filter_map(ToPrimitive::to_f64).sum();
+        if total <= 0.0 {
+            // Every point is already a centroid
+            break;
+        }
+
+        let mut r = rng.next_f64() * total;
+        let mut chosen = size - 1;
+        for (i, d) in nearest.iter().enumerate() {
+            r -= d.to_f64().unwrap_or(0.0);
+            if r < 0.0 {
+                chosen = i;
+                break;
+            }
+        }
+
+        let centroid = sample[chosen].as_vec();
+        for (n, x) in nearest.iter_mut().zip(&sample) {
+            let d = squared_distance(&centroid, *x);
+            if d < *n {
+                *n = d;
+            }
+        }
+        centroids.push(centroid);
+    }
+
+    // Lloyd's algorithm
+    let dims = centroids[0].len();
+    let mut assignments = vec![usize::MAX; size];
+    for _ in 0..ITERATIONS {
+        let mut changed = false;
+        for (a, x) in assignments.iter_mut().zip(&sample) {
+            let c = nearest_centroid(&centroids, *x);
+            if c != *a {
+                *a = c;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![vec![zero::<T::Value>(); dims]; centroids.len()];
+        let mut counts = vec![0usize; centroids.len()];
+        for (&a, x) in assignments.iter().zip(&sample) {
+            for (i, s) in sums[a].iter_mut().enumerate() {
+                *s += x.coord(i);
+            }
+            counts[a] += 1;
+        }
+
+        // Empty cells keep their old centroids
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            if count > 0 {
+                let count = <T::Value as NumCast>::from(count).unwrap();
+                *centroid = sum.into_iter().map(|s| s / count).collect();
+            }
+        }
+    }
+
+    centroids
+}
+
+/// A cell of an IVF index.
+#[derive(Debug)]
+struct IvfCell<T, V> {
+    /// The centroid of this cell.
+    centroid: Vec<V>,
+    /// The posting list of items assigned to this cell.
+    items: Vec<T>,
+}
+
+/// An [inverted file] index.
+///
+/// The items are clustered with [k-means] into `nlist` cells, and each cell keeps a posting list of
+/// the items nearest to its centroid.  Searches find the `nprobe` centroids nearest to the target,
+/// by exhaustive search, and consider every item in their cells.  Probing more cells gives more
+/// accurate results.
+///
+/// Cells are assigned by Euclidean distance between coordinates, but the items themselves are
+/// compared with their own [`Proximity`].
+///
+/// [inverted file]: https://en.wikipedia.org/wiki/Inverted_index
+/// [k-means]: https://en.wikipedia.org/wiki/K-means_clustering
+#[derive(Debug)]
+pub struct Ivf<T: Coordinates> {
+    /// The cells of the index.
+    cells: Vec<IvfCell<T, T::Value>>,
+    /// The number of indexed items.
+    len: usize,
+    /// The number of cells to probe during a search.
+    nprobe: usize,
+}
+
+impl<T> Ivf<T>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    /// Create an index out of a sequence of items, with about `$\sqrt{n}$` cells.
+    pub fn balanced<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let items: Vec<_> = items.into_iter().collect();
+        let nlist = (items.len() as f64).sqrt().ceil() as usize;
+        Self::with_params(nlist, items)
+    }
+
+    /// Create an index out of a sequence of items.
+    ///
+    /// * `nlist`: The number of cells to cluster the items into.
+    /// * `items`: The items to index.
+    pub fn with_params<I: IntoIterator<Item = T>>(nlist: usize, items: I) -> Self {
+        let items: Vec<_> = items.into_iter().collect();
+
+        let cells = if items.is_empty() {
+            Vec::new()
+        } else {
+            kmeans(&items, nlist.max(1))
+                .into_iter()
+                .map(|centroid| IvfCell {
+                    centroid,
+                    items: Vec::new(),
+                })
+                .collect()
+        };
+
+        let mut index = Self {
+            cells,
+            len: 0,
+            nprobe: DEFAULT_NPROBE,
+        };
+        index.extend(items);
+        index
+    }
+
+    /// Add a new item to the index.
+    ///
+    /// The item is assigned to the cell with the nearest centroid, without retraining.
+    pub fn push(&mut self, item: T) {
+        if self.cells.is_empty() {
+            self.cells.push(IvfCell {
+                centroid: item.as_vec(),
+                items: Vec::new(),
+            });
+        }
+
+        let cell = self
+            .cells
+            .iter_mut()
+            .min_by_key(|c| Ordered::new(squared_distance(&c.centroid, &item)))
+            .unwrap();
+        cell.items.push(item);
+        self.len += 1;
+    }
+}
+
+impl<T: Coordinates> Ivf<T> {
+    /// Get the number of cells in this index.
+    pub fn nlist(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Get the number of cells probed during a search.
+    pub fn nprobe(&self) -> usize {
+        self.nprobe
+    }
+
+    /// Set the number of cells probed during a search.
+    ///
+    /// Larger values give more accurate results, at the expense of slower searches.
+    pub fn set_nprobe(&mut self, nprobe: usize) {
+        self.nprobe = nprobe.max(1);
+    }
+
+    /// Get the size of this index.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if this index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Extend<T> for Ivf<T>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for Ivf<T>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::balanced(items)
+    }
+}
+
+impl<T: Coordinates> IntoIterator for Ivf<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let items: Vec<_> = self.cells.into_iter().flat_map(|c| c.items).collect();
+        items.into_iter()
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for Ivf<V>
+where
+    K: Proximity<V> + Coordinates<Value = V::Value>,
+    V: Coordinates,
+    V::Value: Real,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        let target = neighborhood.target();
+
+        let mut cells: Vec<_> = self
+            .cells
+            .iter()
+            .map(|c| (Ordered::new(squared_distance(&c.centroid, target)), c))
+            .collect();
+        cells.sort_unstable_by_key(|&(d, _)| d);
+        cells.truncate(self.nprobe);
+
+        for (_, cell) in cells {
+            for item in &cell.items {
+                if neighborhood.is_exhausted() {
+                    return neighborhood;
+                }
+                neighborhood.consider_bounded(item);
+            }
+        }
+
+        neighborhood
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::euclid::Euclidean;
+    use crate::tests::test_nearest_neighbors;
+
+    #[test]
+    fn test_kmeans() {
+        let mut points = Vec::new();
+        for &(x, y) in &[(0.0, 0.0), (0.0, 10.0), (10.0, 0.0), (10.0, 10.0)] {
+            for i in 0..16 {
+                let t = i as f64;
+                points.push([x + t.sin(), y + t.cos()]);
+            }
+        }
+
+        let mut centroids = kmeans(&points, 4);
+        assert_eq!(centroids.len(), 4);
+
+        centroids.sort_by_key(|c| (Ordered::new(c[0]), Ordered::new(c[1])));
+        let expected = [[0.0, 0.0], [0.0, 10.0], [10.0, 0.0], [10.0, 10.0]];
+        for (c, e) in centroids.iter().zip(&expected) {
+            assert!(squared_distance(c, e) < 0.5, "{:?} != {:?}", c, e);
+        }
+    }
+
+    #[test]
+    fn test_ivf() {
+        test_nearest_neighbors(|points| {
+            let mut index = Ivf::with_params(8, points);
+            index.set_nprobe(8);
+            index
+        });
+    }
+
+    #[test]
+    fn test_ivf_probe() {
+        let points: Vec<_> = (0..256)
+            .map(|i| {
+                let t = i as f32;
+                Euclidean([t.sin(), t.cos(), t / 256.0])
+            })
+            .collect();
+
+        let index = Ivf::from_iter(points.clone());
+        assert_eq!(index.len(), 256);
+        assert_eq!(index.nlist(), 16);
+
+        for point in &points {
+            let nearest = index.nearest(point).expect("No nearest neighbor found");
+            assert_eq!(nearest.item, point);
+        }
+    }
+}
@@ -1,13 +1,26 @@
 //! [k-d trees](https://en.wikipedia.org/wiki/K-d_tree).
 
+use crate::approx::Approximate;
 use crate::coords::Coordinates;
 use crate::distance::Proximity;
+use crate::flat::{FormatError, Kind, Pod, Reader, Scalar, Writer};
 use crate::lp::Minkowski;
-use crate::util::Ordered;
-use crate::{ExactNeighbors, NearestNeighbors, Neighborhood};
+use crate::util::{BestFirst, Ordered};
+#[cfg(feature = "rayon")]
+use crate::util::PAR_MIN_LEN;
+use crate::{ExactNeighbors, NearestNeighbors, Neighbor, Neighborhood};
 
-use num_traits::Signed;
+use num_traits::{one, zero, Signed};
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+#[cfg(feature = "serde")]
+use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+use std::collections::BinaryHeap;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Write};
 use std::iter::FromIterator;
 use std::ops::Deref;
 
@@ -32,17 +45,32 @@ impl<T: Coordinates> KdNode<T> {
         }
     }
 
-    /// Create a balanced tree.
-    fn balanced<I: IntoIterator<Item = T>>(items: I) -> Option<Self> {
-        let mut nodes: Vec<_> = items
+    /// Create some unlinked nodes out of a sequence of items.
+    fn nodes<I: IntoIterator<Item = T>>(items: I) -> Vec<Option<Box<Self>>> {
+        items
             .into_iter()
             .map(Self::new)
             .map(Box::new)
             .map(Some)
-            .collect();
+            .collect()
+    }
+
+    /// Unlink every node in a list from its descendants, appending them to the list.
+    fn unlink(nodes: &mut Vec<Option<Box<Self>>>) {
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = nodes[i].as_mut().unwrap();
+            let left = node.left.take();
+            let right = node.right.take();
+            if left.is_some() {
+                nodes.push(left);
+            }
+            if right.is_some() {
+                nodes.push(right);
+            }
 
-        Self::balanced_recursive(&mut nodes, 0)
-            .map(|node| *node)
+            i += 1;
+        }
     }
 
     /// Create a balanced subtree.
@@ -51,9 +79,12 @@ impl<T: Coordinates> KdNode<T> {
             return None;
         }
 
-        nodes.sort_unstable_by_key(|x| Ordered::new(x.as_ref().unwrap().item.coord(level)));
+        let mid = nodes.len() / 2;
+        nodes.select_nth_unstable_by_key(mid, |x| {
+            Ordered::new(x.as_ref().unwrap().item.coord(level))
+        });
 
-        let (left, right) = nodes.split_at_mut(nodes.len() / 2);
+        let (left, right) = nodes.split_at_mut(mid);
         let (node, right) = right.split_first_mut().unwrap();
         let mut node = node.take().unwrap();
 
@@ -82,6 +113,69 @@ impl<T: Coordinates> KdNode<T> {
             }
         }
     }
+
+    /// Remove an item from the subtree in a slot, if it's present.
+    fn remove(slot: &mut Option<Box<Self>>, item: &T, level: usize) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let node = slot.as_mut()?;
+
+        if node.item == *item {
+            let mut node = slot.take().unwrap();
+            let mut nodes = vec![node.left.take(), node.right.take()];
+            nodes.retain(Option::is_some);
+            Self::unlink(&mut nodes);
+            *slot = Self::balanced_recursive(&mut nodes, level);
+            return Some(node.item);
+        }
+
+        // Ties with the splitting coordinate can end up on either side
+        let next = (level + 1) % item.dims();
+        let coord = item.coord(level);
+        let split = node.item.coord(level);
+        if coord <= split {
+            if let Some(removed) = Self::remove(&mut node.left, item, next) {
+                return Some(removed);
+            }
+        }
+        if coord >= split {
+            Self::remove(&mut node.right, item, next)
+        } else {
+            None
+        }
+    }
+}
+
+/// A node of a [`KdTree`] in its serialized form, which lists the nodes in pre-order.
+#[cfg(feature = "serde")]
+#[derive(Deserialize, Serialize)]
+#[serde(rename = "KdNode")]
+struct KdNodeData<T> {
+    /// The item stored in this node.
+    item: T,
+    /// Whether this node has a left subtree.
+    left: bool,
+    /// Whether this node has a right subtree.
+    right: bool,
+}
+
+#[cfg(feature = "serde")]
+impl<T> KdNode<T> {
+    /// Iterate over the nodes of this subtree in pre-order, without recursion.
+    fn pre_order(&self) -> impl Iterator<Item = KdNodeData<&T>> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.right.as_deref());
+            stack.extend(node.left.as_deref());
+            Some(KdNodeData {
+                item: &node.item,
+                left: node.left.is_some(),
+                right: node.right.is_some(),
+            })
+        })
+    }
 }
 
 /// Marker trait for [`Proximity`] implementations that are compatible with k-d trees.
@@ -102,12 +196,11 @@ where
     V: Coordinates,
 {}
 
-trait KdSearch<K, V, N>: Copy
+trait KdSearch<K, V>: Copy
 where
     K: KdProximity<V>,
     K::Value: PartialOrd<K::Distance>,
     V: Coordinates + Copy,
-    N: Neighborhood<K, V>,
 {
     /// Get this node's item.
     fn item(self) -> V;
@@ -119,12 +212,24 @@ where
     fn right(self) -> Option<Self>;
 
     /// Recursively search for nearest neighbors.
-    fn search(self, level: usize, neighborhood: &mut N) {
+    ///
+    /// Subtrees are pruned when `factor` times their distance bound is out of range, so a `factor`
+    /// of one gives exact results.
+    fn search<N>(self, depth: usize, factor: K::Value, neighborhood: &mut N)
+    where
+        N: Neighborhood<K, V>,
+    {
+        if neighborhood.is_exhausted() {
+            return;
+        }
+
+        neighborhood.visit(depth);
         let item = self.item();
-        neighborhood.consider(item);
+        neighborhood.consider_bounded(item);
 
         let target = neighborhood.target();
 
+        let level = depth % item.dims();
         let bound = target.coord(level) - item.coord(level);
         let (near, far) = if bound.is_negative() {
             (self.left(), self.right())
@@ -132,26 +237,25 @@ where
             (self.right(), self.left())
         };
 
-        let next = (level + 1) % self.item().dims();
-
         if let Some(near) = near {
-            near.search(next, neighborhood);
+            near.search(depth + 1, factor, neighborhood);
         }
 
         if let Some(far) = far {
-            if neighborhood.contains(bound.abs()) {
-                far.search(next, neighborhood);
+            if neighborhood.contains(bound.abs() * factor) {
+                far.search(depth + 1, factor, neighborhood);
+            } else {
+                neighborhood.prune(depth + 1);
             }
         }
     }
 }
 
-impl<'a, K, V, N> KdSearch<K, &'a V, N> for &'a KdNode<V>
+impl<'a, K, V> KdSearch<K, &'a V> for &'a KdNode<V>
 where
     K: KdProximity<&'a V>,
     K::Value: PartialOrd<K::Distance>,
     V: Coordinates,
-    N: Neighborhood<K, &'a V>,
 {
     fn item(self) -> &'a V {
         &self.item
@@ -166,49 +270,147 @@ where
     }
 }
 
+/// An entry in the queue of a best-first k-d tree traversal.
+type KdEntry<'v, K, V, S> =
+    BestFirst<(S, usize), &'v V, <K as Coordinates>::Value, <K as Proximity<V>>::Distance>;
+
+/// A best-first traversal of a k-d tree, in order of distance from a target.
+struct KdBrowse<'k, 'v, K: Coordinates + Proximity<V>, V, S> {
+    /// The target of the traversal.
+    target: &'k K,
+    /// The subtrees (with their levels) and items left to visit.
+    queue: BinaryHeap<KdEntry<'v, K, V, S>>,
+}
+
+impl<'k, 'v, K, V, S> KdBrowse<'k, 'v, K, V, S>
+where
+    K: KdProximity<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+    S: KdSearch<&'k K, &'v V>,
+{
+    /// Start a traversal from the root of a tree.
+    fn new(target: &'k K, root: Option<S>) -> Self {
+        let mut queue = BinaryHeap::new();
+        if let Some(root) = root {
+            queue.push(BestFirst::Node((root, 0), zero()));
+        }
+
+        Self { target, queue }
+    }
+
+    /// Find the next nearest neighbor.
+    fn next(&mut self) -> Option<Neighbor<&'v V, K::Distance>> {
+        while let Some(entry) = self.queue.pop() {
+            let ((node, level), bound) = match entry {
+                BestFirst::Item(item, distance) => return Some(Neighbor::new(item, distance)),
+                BestFirst::Node(node, bound) => (node, bound),
+            };
+
+            let item = node.item();
+            let distance = self.target.distance(item);
+            self.queue.push(BestFirst::Item(item, distance));
+
+            let diff = self.target.coord(level) - item.coord(level);
+            let (near, far) = if diff.is_negative() {
+                (node.left(), node.right())
+            } else {
+                (node.right(), node.left())
+            };
+
+            let next = (level + 1) % item.dims();
+
+            if let Some(near) = near {
+                self.queue.push(BestFirst::Node((near, next), bound));
+            }
+
+            // Everything on the far side is at least |diff| away
+            if let Some(far) = far {
+                let diff = diff.abs();
+                let bound = if <K::Value as PartialOrd>::gt(&diff, &bound) {
+                    diff
+                } else {
+                    bound
+                };
+                self.queue.push(BestFirst::Node((far, next), bound));
+            }
+        }
+
+        None
+    }
+}
+
+impl<'k, 'v, K, V, S> Debug for KdBrowse<'k, 'v, K, V, S>
+where
+    K: Coordinates + Proximity<V> + Debug,
+    K::Value: Debug,
+    K::Distance: Debug,
+    V: Debug,
+    S: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("KdBrowse")
+            .field("target", &self.target)
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
 /// A [k-d tree](https://en.wikipedia.org/wiki/K-d_tree).
 #[derive(Debug)]
 pub struct KdTree<T> {
     root: Option<KdNode<T>>,
+    len: usize,
 }
 
 impl<T: Coordinates> KdTree<T> {
     /// Create an empty tree.
     pub fn new() -> Self {
-        Self { root: None }
+        Self {
+            root: None,
+            len: 0,
+        }
     }
 
     /// Create a balanced tree out of a sequence of items.
     pub fn balanced<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut nodes = KdNode::nodes(items);
+        Self::from_nodes(&mut nodes)
+    }
+
+    /// Create a balanced tree out of some unlinked nodes.
+    fn from_nodes(nodes: &mut [Option<Box<KdNode<T>>>]) -> Self {
         Self {
-            root: KdNode::balanced(items),
+            root: KdNode::balanced_recursive(nodes, 0).map(|node| *node),
+            len: nodes.len(),
         }
     }
 
-    /// Rebalance this k-d tree.
-    pub fn balance(&mut self) {
+    /// Take all the nodes out of this tree, unlinked from each other.
+    fn take_nodes(&mut self) -> Vec<Option<Box<KdNode<T>>>> {
         let mut nodes = Vec::new();
         if let Some(root) = self.root.take() {
             nodes.push(Some(Box::new(root)));
         }
+        KdNode::unlink(&mut nodes);
+        self.len = 0;
+        nodes
+    }
 
-        let mut i = 0;
-        while i < nodes.len() {
-            let node = nodes[i].as_mut().unwrap();
-            let inside = node.left.take();
-            let outside = node.right.take();
-            if inside.is_some() {
-                nodes.push(inside);
-            }
-            if outside.is_some() {
-                nodes.push(outside);
-            }
+    /// Rebalance this k-d tree.
+    pub fn balance(&mut self) {
+        let mut nodes = self.take_nodes();
+        *self = Self::from_nodes(&mut nodes);
+    }
 
-            i += 1;
-        }
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        self.root = KdNode::balanced_recursive(&mut nodes, 0)
-            .map(|node| *node);
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     /// Push a new item into the tree.
@@ -221,6 +423,94 @@ impl<T: Coordinates> KdTree<T> {
         } else {
             self.root = Some(KdNode::new(item));
         }
+        self.len += 1;
+    }
+
+    /// Remove an item from the tree, returning it if it was present.
+    ///
+    /// The subtree below the removed item is rebuilt, so removals near the root are expensive.
+    /// Use [`KdTree::retain()`] to remove many items at once.
+    pub fn remove(&mut self, item: &T) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let mut root = self.root.take().map(Box::new);
+        let removed = KdNode::remove(&mut root, item, 0);
+        self.root = root.map(|node| *node);
+
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Retain only the items that satisfy a predicate, rebalancing the tree.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let mut nodes = self.take_nodes();
+        nodes.retain(|node| f(&node.as_ref().unwrap().item));
+        *self = Self::from_nodes(&mut nodes);
+    }
+
+    /// Iterate over the items in this tree, from nearest to farthest from a target.
+    ///
+    /// The neighbors are found lazily by a best-first search, so this is useful when the number of
+    /// neighbors needed isn't known up front.  The order is only guaranteed to be exact for
+    /// [Minkowski] distances.
+    pub fn nearest_iter<'k, 'v, K>(&'v self, target: &'k K) -> NearestIter<'k, 'v, K, T>
+    where
+        K: KdProximity<T>,
+        K::Value: PartialOrd<K::Distance>,
+    {
+        NearestIter(KdBrowse::new(target, self.root.as_ref()))
+    }
+}
+
+/// Serializes the nodes of the tree in pre-order, so that even very deep trees can be serialized
+/// without recursion.
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for KdTree<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        for node in self.root.iter().flat_map(KdNode::pre_order) {
+            seq.serialize_element(&node)?;
+        }
+        seq.end()
+    }
+}
+
+/// Deserializes the tree exactly as it was serialized, after checking that its layout is valid.
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for KdTree<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let nodes: Vec<KdNodeData<T>> = Vec::deserialize(deserializer)?;
+        let len = nodes.len();
+        let invalid = || de::Error::custom("invalid KdTree layout");
+
+        // Build the subtrees from the bottom up, so the left one is always on top of the stack
+        let mut subtrees = Vec::new();
+        for node in nodes.into_iter().rev() {
+            let left = if node.left {
+                Some(subtrees.pop().ok_or_else(invalid)?)
+            } else {
+                None
+            };
+            let right = if node.right {
+                Some(subtrees.pop().ok_or_else(invalid)?)
+            } else {
+                None
+            };
+            subtrees.push(Box::new(KdNode {
+                item: node.item,
+                left,
+                right,
+            }));
+        }
+
+        if subtrees.len() > 1 {
+            return Err(invalid());
+        }
+        let root = subtrees.pop().map(|node| *node);
+        Ok(Self { root, len })
     }
 }
 
@@ -237,7 +527,7 @@ impl<T: Coordinates> Extend<T> for KdTree<T> {
                 self.push(item);
             }
         } else {
-            self.root = KdNode::balanced(items);
+            *self = Self::balanced(items);
         }
     }
 }
@@ -300,7 +590,7 @@ where
         N: Neighborhood<&'k K, &'v V>,
     {
         if let Some(root) = &self.root {
-            root.search(0, &mut neighborhood);
+            root.search(0, one(), &mut neighborhood);
         }
         neighborhood
     }
@@ -314,8 +604,63 @@ where
     V: Coordinates,
 {}
 
+/// (1 + ε)-approximate searches in a k-d tree.
+impl<'a, K, V> NearestNeighbors<K, V> for Approximate<'a, KdTree<V>, V::Value>
+where
+    K: KdProximity<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if let Some(root) = &self.index().root {
+            root.search(0, self.factor(), &mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// An iterator over the items in a k-d tree, from nearest to farthest from a target.
+///
+/// Created by [`KdTree::nearest_iter()`].
+pub struct NearestIter<'k, 'v, K: Coordinates + Proximity<V>, V>(
+    KdBrowse<'k, 'v, K, V, &'v KdNode<V>>,
+);
+
+impl<'k, 'v, K, V> Debug for NearestIter<'k, 'v, K, V>
+where
+    K: Coordinates + Proximity<V> + Debug,
+    K::Value: Debug,
+    K::Distance: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("NearestIter")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<'k, 'v, K, V> Iterator for NearestIter<'k, 'v, K, V>
+where
+    K: KdProximity<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{
+    type Item = Neighbor<&'v V, K::Distance>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
 /// A node in a flat k-d tree.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 struct FlatKdNode<T> {
     /// The item stored in this node.
     item: T,
@@ -347,9 +692,8 @@ impl<T: Coordinates> FlatKdNode<T> {
     /// Create a balanced subtree.
     fn balance_recursive(nodes: &mut [Self], level: usize) {
         if !nodes.is_empty() {
-            nodes.sort_unstable_by_key(|x| Ordered::new(x.item.coord(level)));
-
             let mid = nodes.len() / 2;
+            nodes.select_nth_unstable_by_key(mid, |x| Ordered::new(x.item.coord(level)));
             nodes.swap(0, mid);
 
             let (node, children) = nodes.split_first_mut().unwrap();
@@ -363,12 +707,70 @@ impl<T: Coordinates> FlatKdNode<T> {
     }
 }
 
-impl<'a, K, V, N> KdSearch<K, &'a V, N> for &'a [FlatKdNode<V>]
+#[cfg(feature = "rayon")]
+impl<T> FlatKdNode<T>
+where
+    T: Coordinates + Send,
+    T::Value: Send,
+{
+    /// Create a balanced tree in parallel.
+    fn par_balanced<I: IntoParallelIterator<Item = T>>(items: I) -> Vec<Self> {
+        let mut nodes: Vec<_> = items
+            .into_par_iter()
+            .map(Self::new)
+            .collect();
+
+        Self::par_balance_recursive(&mut nodes, 0);
+
+        nodes
+    }
+
+    /// Create a balanced subtree in parallel.
+    fn par_balance_recursive(nodes: &mut [Self], level: usize) {
+        if nodes.len() < PAR_MIN_LEN {
+            return Self::balance_recursive(nodes, level);
+        }
+
+        let mid = nodes.len() / 2;
+        nodes.select_nth_unstable_by_key(mid, |x| Ordered::new(x.item.coord(level)));
+        nodes.swap(0, mid);
+
+        let (node, children) = nodes.split_first_mut().unwrap();
+        let (left, right) = children.split_at_mut(mid);
+        node.left_len = left.len();
+
+        let next = (level + 1) % node.item.dims();
+        rayon::join(
+            || Self::par_balance_recursive(left, next),
+            || Self::par_balance_recursive(right, next),
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> FlatKdNode<T> {
+    /// Check that the subtree sizes of a flat tree are consistent.
+    fn is_valid(nodes: &[Self]) -> bool {
+        let mut stack = vec![nodes];
+        while let Some(nodes) = stack.pop() {
+            if let Some((node, children)) = nodes.split_first() {
+                if node.left_len > children.len() {
+                    return false;
+                }
+                let (left, right) = children.split_at(node.left_len);
+                stack.push(left);
+                stack.push(right);
+            }
+        }
+        true
+    }
+}
+
+impl<'a, K, V> KdSearch<K, &'a V> for &'a [FlatKdNode<V>]
 where
     K: KdProximity<&'a V>,
     K::Value: PartialOrd<K::Distance>,
     V: Coordinates,
-    N: Neighborhood<K, &'a V>,
 {
     fn item(self) -> &'a V {
         &self[0].item
@@ -411,6 +813,92 @@ impl<T: Coordinates> FlatKdTree<T> {
             nodes: FlatKdNode::balanced(items),
         }
     }
+
+    /// Create a balanced tree out of a sequence of items, in parallel.
+    ///
+    /// This builds a tree like [`balanced()`](Self::balanced), using the current [rayon] thread
+    /// pool.  Only available with the `rayon` feature.
+    ///
+    /// [rayon]: https://docs.rs/rayon
+    #[cfg(feature = "rayon")]
+    pub fn par_balanced<I: IntoParallelIterator<Item = T>>(items: I) -> Self
+    where
+        T: Send,
+        T::Value: Send,
+    {
+        Self {
+            nodes: FlatKdNode::par_balanced(items),
+        }
+    }
+
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Write this tree in the zero-copy [flat format](crate::flat).
+    ///
+    /// Every item must have the same number of coordinates.  The tree is written in many small
+    /// pieces, so `writer` should usually be buffered.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        T::Value: Scalar,
+    {
+        let dims = self.nodes.first().map_or(0, |node| node.item.dims());
+        let coords = T::Value::TAG;
+        let mut writer = Writer::new(writer, Kind::Kd, coords, 0, dims, self.nodes.len())?;
+
+        for node in &self.nodes {
+            writer.write_item(&node.item)?;
+        }
+        writer.end_section()?;
+
+        for node in &self.nodes {
+            writer.write_len(node.left_len)?;
+        }
+        writer.end_section()
+    }
+
+    /// Iterate over the items in this tree, from nearest to farthest from a target.
+    ///
+    /// See [`KdTree::nearest_iter()`].
+    pub fn nearest_iter<'k, 'v, K>(&'v self, target: &'k K) -> FlatNearestIter<'k, 'v, K, T>
+    where
+        K: KdProximity<T>,
+        K::Value: PartialOrd<K::Distance>,
+    {
+        let root = if self.nodes.is_empty() {
+            None
+        } else {
+            Some(self.nodes.as_slice())
+        };
+        FlatNearestIter(KdBrowse::new(target, root))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for FlatKdTree<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.nodes.serialize(serializer)
+    }
+}
+
+/// Deserializes the tree exactly as it was serialized, after checking that its layout is valid.
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FlatKdTree<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let nodes = Vec::deserialize(deserializer)?;
+        if FlatKdNode::is_valid(&nodes) {
+            Ok(Self { nodes })
+        } else {
+            Err(de::Error::custom("invalid FlatKdTree layout"))
+        }
+    }
 }
 
 impl<T: Coordinates> FromIterator<T> for FlatKdTree<T> {
@@ -440,6 +928,40 @@ impl<T> IntoIterator for FlatKdTree<T> {
     }
 }
 
+/// An iterator over the items in a flat k-d tree, from nearest to farthest from a target.
+///
+/// Created by [`FlatKdTree::nearest_iter()`].
+pub struct FlatNearestIter<'k, 'v, K: Coordinates + Proximity<V>, V>(
+    KdBrowse<'k, 'v, K, V, &'v [FlatKdNode<V>]>,
+);
+
+impl<'k, 'v, K, V> Debug for FlatNearestIter<'k, 'v, K, V>
+where
+    K: Coordinates + Proximity<V> + Debug,
+    K::Value: Debug,
+    K::Distance: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("FlatNearestIter")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<'k, 'v, K, V> Iterator for FlatNearestIter<'k, 'v, K, V>
+where
+    K: KdProximity<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{
+    type Item = Neighbor<&'v V, K::Distance>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
 impl<K, V> NearestNeighbors<K, V> for FlatKdTree<V>
 where
     K: KdProximity<V>,
@@ -453,7 +975,7 @@ where
         N: Neighborhood<&'k K, &'v V>,
     {
         if !self.nodes.is_empty() {
-            self.nodes.as_slice().search(0, &mut neighborhood);
+            self.nodes.as_slice().search(0, one(), &mut neighborhood);
         }
         neighborhood
     }
@@ -467,11 +989,161 @@ where
     V: Coordinates,
 {}
 
+/// (1 + ε)-approximate searches in a flat k-d tree.
+impl<'a, K, V> NearestNeighbors<K, V> for Approximate<'a, FlatKdTree<V>, V::Value>
+where
+    K: KdProximity<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        let nodes = &self.index().nodes;
+        if !nodes.is_empty() {
+            nodes.as_slice().search(0, self.factor(), &mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// The nodes of a flat k-d tree in the [flat format](crate::flat).
+#[derive(Debug)]
+struct FlatKdSlices<'a, T> {
+    /// The items.
+    items: &'a [T],
+    /// The sizes of the left subtrees.
+    left_lens: &'a [u64],
+}
+
+impl<'a, T> FlatKdSlices<'a, T> {
+    /// Get the nodes in a range.
+    fn slice(self, start: usize, end: usize) -> Self {
+        Self {
+            items: &self.items[start..end],
+            left_lens: &self.left_lens[start..end],
+        }
+    }
+}
+
+impl<'a, T> Clone for FlatKdSlices<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for FlatKdSlices<'a, T> {}
+
+impl<'a, K, V> KdSearch<K, &'a V> for FlatKdSlices<'a, V>
+where
+    K: KdProximity<&'a V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{
+    fn item(self) -> &'a V {
+        &self.items[0]
+    }
+
+    fn left(self) -> Option<Self> {
+        let end = (self.left_lens[0] as usize).saturating_add(1);
+        if end > 1 {
+            Some(self.slice(1, end))
+        } else {
+            None
+        }
+    }
+
+    fn right(self) -> Option<Self> {
+        let start = (self.left_lens[0] as usize).saturating_add(1);
+        if start < self.items.len() {
+            Some(self.slice(start, self.items.len()))
+        } else {
+            None
+        }
+    }
+}
+
+/// A [`FlatKdTree`] borrowed from a byte buffer in the zero-copy [flat format](crate::flat).
+///
+/// The items are read in place, so they must be [plain old data](Pod).  A tree written from items
+/// with a dynamic number of coordinates, like `Euclidean<Vec<f32>>`, can be read back with an
+/// array type of the same width, like `Euclidean<[f32; 128]>`.
+#[derive(Debug)]
+pub struct FlatKdTreeRef<'a, T> {
+    nodes: FlatKdSlices<'a, T>,
+}
+
+impl<'a, T: Pod> FlatKdTreeRef<'a, T> {
+    /// Open a tree stored in a byte buffer.
+    ///
+    /// Only the header is checked, so this takes constant time.  The buffer must be aligned for
+    /// the item type.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let mut reader = Reader::new::<T>(bytes, Kind::Kd, 0)?;
+        let items = reader.section()?;
+        let left_lens = reader.section()?;
+
+        Ok(Self {
+            nodes: FlatKdSlices { items, left_lens },
+        })
+    }
+}
+
+impl<'a, T> FlatKdTreeRef<'a, T> {
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.nodes.items.len()
+    }
+
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.items.is_empty()
+    }
+}
+
+impl<'a, K, V> NearestNeighbors<K, V> for FlatKdTreeRef<'a, V>
+where
+    K: KdProximity<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if !self.is_empty() {
+            let nodes: FlatKdSlices<'v, V> = self.nodes;
+            nodes.search(0, one(), &mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// k-d trees are exact for [Minkowski] distances.
+impl<'a, K, V> ExactNeighbors<K, V> for FlatKdTreeRef<'a, V>
+where
+    K: KdProximity<V> + Minkowski<V>,
+    K::Value: PartialOrd<K::Distance>,
+    V: Coordinates,
+{}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::tests::test_exact_neighbors;
+    use crate::distance::Distance;
+    use crate::euclid::Euclidean;
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::flat::tests::aligned;
+    use crate::tests::{test_exact_neighbors, test_remove, test_retain};
+    use crate::{SingletonNeighborhood, StatsNeighborhood};
+
+    use rand::prelude::*;
 
     #[test]
     fn test_kd_tree() {
@@ -489,8 +1161,211 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_kd_tree_remove() {
+        test_remove(KdTree::from_iter, KdTree::remove, KdTree::len);
+    }
+
+    #[test]
+    fn test_kd_tree_remove_root() {
+        let points: Vec<_> = (0..256)
+            .map(|_| Euclidean([random::<f32>(), random(), random()]))
+            .collect();
+        let mut tree = KdTree::from_iter(points.clone());
+        let mut exhaustive = ExhaustiveSearch::from_iter(points);
+
+        for _ in 0..32 {
+            // Remove the root, and one of its children, which has children of its own at first
+            let root = tree.root.as_ref().unwrap();
+            let child = root.left.as_ref().or(root.right.as_ref()).unwrap();
+            for item in &[root.item, child.item] {
+                assert_eq!(tree.remove(item).as_ref(), Some(item));
+                assert_eq!(exhaustive.remove(item).as_ref(), Some(item));
+            }
+
+            let target = Euclidean([random(), random(), random()]);
+            assert_eq!(tree.k_nearest(&target, 3), exhaustive.k_nearest(&target, 3));
+        }
+        assert_eq!(tree.len(), 192);
+    }
+
+    #[test]
+    fn test_kd_tree_retain() {
+        let from_iter = |points| {
+            let mut tree = KdTree::new();
+            for point in points {
+                tree.push(point);
+            }
+            tree
+        };
+        test_retain(from_iter, |tree, f| tree.retain(f), KdTree::len);
+    }
+
     #[test]
     fn test_flat_kd_tree() {
         test_exact_neighbors(FlatKdTree::from_iter);
     }
+
+    #[test]
+    fn test_flat_kd_tree_ref() {
+        test_exact_neighbors(|points| {
+            let mut bytes = Vec::new();
+            FlatKdTree::from_iter(points).write_to(&mut bytes).unwrap();
+            FlatKdTreeRef::<Euclidean<[f32; 3]>>::from_bytes(aligned(&bytes)).unwrap()
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_par_flat_kd_tree() {
+        test_exact_neighbors(FlatKdTree::par_balanced);
+
+        let points: Vec<Euclidean<[f32; 3]>> = (0..(4 * PAR_MIN_LEN))
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let tree = FlatKdTree::balanced(points.clone());
+        let par_tree = FlatKdTree::par_balanced(points);
+        assert!(tree.into_iter().eq(par_tree));
+    }
+
+    #[test]
+    fn test_nearest_iter() {
+        let points: Vec<Euclidean<[f32; 3]>> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let target = Euclidean([random(), random(), random()]);
+
+        let eindex = ExhaustiveSearch::from_iter(points.clone());
+        let expected = eindex.k_nearest(&target, points.len());
+
+        let empty = KdTree::<Euclidean<[f32; 3]>>::new();
+        assert!(empty.nearest_iter(&target).next().is_none());
+
+        let tree = KdTree::from_iter(points.clone());
+        let neighbors: Vec<_> = tree.nearest_iter(&target).collect();
+        assert_eq!(neighbors, expected);
+
+        let mut tree = KdTree::new();
+        tree.extend(points.iter().copied());
+        for point in &points {
+            tree.push(*point);
+        }
+        let neighbors: Vec<_> = tree.nearest_iter(&target).step_by(2).collect();
+        assert_eq!(neighbors, expected);
+
+        let flat = FlatKdTree::from_iter(points.clone());
+        let neighbors: Vec<_> = flat.nearest_iter(&target).take(10).collect();
+        assert_eq!(neighbors, &expected[..10]);
+    }
+
+    #[test]
+    fn test_approximate() {
+        let points: Vec<Euclidean<[f32; 3]>> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let eindex = ExhaustiveSearch::from_iter(points.clone());
+        let tree = KdTree::from_iter(points.clone());
+        let flat = FlatKdTree::from_iter(points.clone());
+
+        for _ in 0..16 {
+            let target = Euclidean([random(), random(), random()]);
+            let expected = eindex.k_nearest(&target, 3);
+
+            assert_eq!(Approximate::new(&tree, 0.0).k_nearest(&target, 3), expected);
+            assert_eq!(Approximate::new(&flat, 0.0).k_nearest(&target, 3), expected);
+
+            let approx = Approximate::new(&tree, 1.0);
+            let neighbors = approx.k_nearest(&target, 3);
+            let flat_approx = Approximate::new(&flat, 1.0);
+            let flat_neighbors = flat_approx.k_nearest(&target, 3);
+            assert_eq!(neighbors.len(), 3);
+            assert_eq!(flat_neighbors.len(), 3);
+            for ((n, f), e) in neighbors.iter().zip(&flat_neighbors).zip(&expected) {
+                assert!(n.distance <= 2.0 * e.distance.value());
+                assert!(f.distance <= 2.0 * e.distance.value());
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let points: Vec<Euclidean<[f32; 3]>> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let tree = KdTree::from_iter(points.clone());
+        let flat = FlatKdTree::from_iter(points.clone());
+        let target = Euclidean([random(), random(), random()]);
+
+        let singleton = SingletonNeighborhood::new(&target, None);
+        let stats = tree.search(StatsNeighborhood::new(singleton)).stats();
+        assert_eq!(stats.distance_evals, stats.nodes_visited);
+        assert!(stats.nodes_visited < points.len());
+        assert!(stats.subtrees_pruned > 0);
+        assert!(stats.max_depth <= 8);
+
+        let singleton = SingletonNeighborhood::new(&target, None);
+        let flat_stats = flat.search(StatsNeighborhood::new(singleton)).stats();
+        assert_eq!(flat_stats, stats);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_kd_tree_serde() {
+        test_exact_neighbors(|points| {
+            let index = KdTree::from_iter(points);
+            let json = serde_json::to_string(&index).unwrap();
+            let copy: KdTree<_> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+            copy
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deep_kd_tree_serde() {
+        let mut tree = KdTree::new();
+        for i in 0..1000 {
+            tree.push(Euclidean([i as f32, 0.0, 0.0]));
+        }
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let copy: KdTree<Euclidean<[f32; 3]>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+        assert_eq!(copy.len(), 1000);
+
+        let target = Euclidean([500.2, 0.0, 0.0]);
+        assert_eq!(copy.nearest(&target).unwrap().item, &Euclidean([500.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_kd_tree_invalid() {
+        let json = r#"[{"item":[0.0,0.0,0.0],"left":true,"right":false}]"#;
+        assert!(serde_json::from_str::<KdTree<Euclidean<[f32; 3]>>>(json).is_err());
+
+        let json = r#"[
+            {"item":[0.0,0.0,0.0],"left":false,"right":false},
+            {"item":[1.0,0.0,0.0],"left":false,"right":false}
+        ]"#;
+        assert!(serde_json::from_str::<KdTree<Euclidean<[f32; 3]>>>(json).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_flat_kd_tree_serde() {
+        test_exact_neighbors(|points| {
+            let index = FlatKdTree::from_iter(points);
+            let json = serde_json::to_string(&index).unwrap();
+            let copy: FlatKdTree<_> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+            copy
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_flat_kd_tree_invalid() {
+        let json = r#"[{"item":[0.0,0.0,0.0],"left_len":1}]"#;
+        assert!(serde_json::from_str::<FlatKdTree<Euclidean<[f32; 3]>>>(json).is_err());
+    }
 }
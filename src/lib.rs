@@ -70,7 +70,9 @@
 //!     assert_eq!(nearest.distance, 5);
 //!
 //! [`NearestNeighbors`] also provides the [`nearest_within()`], [`k_nearest()`], and
-//! [`k_nearest_within()`] methods which find up to `k` neighbors within a possible threshold.
+//! [`k_nearest_within()`] methods which find up to `k` neighbors within a possible threshold, and
+//! the [`within()`] method which finds every neighbor within a radius.  With the optional `rayon`
+//! feature, the `par` module adds parallel batch versions of these searches.
 //!
 //! It can be expensive to compute nearest neighbors exactly, especially in high dimensions.
 //! For performance reasons, [`NearestNeighbors`] implementations are allowed to return approximate
@@ -118,16 +120,31 @@
 //! [`k_nearest()`]: NearestNeighbors#method.k_nearest
 //! [`nearest_within()`]: NearestNeighbors#method.nearest_within
 //! [`k_nearest_within()`]: NearestNeighbors#method.k_nearest_within
+//! [`within()`]: NearestNeighbors#method.within
 
+pub mod approx;
+pub mod ball;
+pub mod bk;
 pub mod chebyshev;
 pub mod coords;
 pub mod cos;
+pub mod cover;
 pub mod distance;
+pub mod edit;
 pub mod euclid;
 pub mod exhaustive;
+pub mod flat;
+pub mod geo;
 pub mod hamming;
+pub mod hnsw;
+pub mod ivf;
 pub mod kd;
 pub mod lp;
+pub mod lsh;
+#[cfg(feature = "rayon")]
+pub mod par;
+pub mod pq;
+pub mod rp;
 pub mod taxi;
 pub mod vp;
 
@@ -137,6 +154,9 @@ pub use coords::Coordinates;
 pub use distance::{Distance, Metric, Proximity};
 pub use euclid::{euclidean_distance, Euclidean, EuclideanDistance};
 
+use util::Ordered;
+
+use std::cell::Cell;
 use std::convert::TryInto;
 
 /// A nearest neighbor.
@@ -177,6 +197,11 @@ where
 /// They also serve as a customization point, allowing for functionality to be injected into any
 /// [NearestNeighbors] implementation (for example, filtering the result set or limiting the number
 /// of neighbors considered).
+///
+/// The neighborhoods used by the [NearestNeighbors] methods are [SingletonNeighborhood],
+/// [HeapNeighborhood], and [RadiusNeighborhood].  They can be customized by wrappers like
+/// [FilterNeighborhood], [ExcludeNeighborhood], [MapNeighborhood], [BudgetNeighborhood], and
+/// [StatsNeighborhood], and passed directly to [NearestNeighbors::search].
 pub trait Neighborhood<K: Proximity<V>, V> {
     /// Returns the target of the nearest neighbor search.
     fn target(&self) -> K;
@@ -190,11 +215,51 @@ pub trait Neighborhood<K: Proximity<V>, V> {
     ///
     /// Returns `self.target().distance(item)`.
     fn consider(&mut self, item: V) -> K::Distance;
+
+    /// Consider a new candidate neighbor, without computing its exact distance unless necessary.
+    ///
+    /// The current search radius is passed to [`Proximity::distance_bounded()`], which may give up
+    /// early on items that are too far away.  [NearestNeighbors] implementations should prefer
+    /// this to [`consider()`](Self::consider) whenever they don't need the distance for pruning.
+    ///
+    /// By default, this just calls [`consider()`](Self::consider).
+    fn consider_bounded(&mut self, item: V) {
+        self.consider(item);
+    }
+
+    /// Check whether the search should stop without considering any more candidates.
+    ///
+    /// This is always `false` by default.  Neighborhoods that limit the work done by a search, like
+    /// [BudgetNeighborhood], return `true` once that limit is reached.  [NearestNeighbors]
+    /// implementations should check it before considering new candidates.
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+
+    /// Get the maximum number of neighbors this neighborhood can hold, if it is limited.
+    ///
+    /// This is `None` by default.  Approximate [NearestNeighbors] implementations can use it to
+    /// collect enough candidates to fill the neighborhood.
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Record that the search visited a node of an index, at the given depth.
+    ///
+    /// This does nothing by default.  Tree-based [NearestNeighbors] implementations call it, along
+    /// with [`prune()`](Self::prune), so that wrappers like [StatsNeighborhood] can measure their
+    /// searches.
+    fn visit(&mut self, _depth: usize) {}
+
+    /// Record that the search pruned a subtree rooted at the given depth.
+    ///
+    /// This does nothing by default.
+    fn prune(&mut self, _depth: usize) {}
 }
 
 /// A [Neighborhood] with at most one result.
 #[derive(Debug)]
-struct SingletonNeighborhood<K, V, D> {
+pub struct SingletonNeighborhood<K, V, D> {
     /// The search target.
     target: K,
     /// The current threshold distance.
@@ -208,7 +273,7 @@ impl<K, V, D> SingletonNeighborhood<K, V, D> {
     ///
     /// * `target`: The search target.
     /// * `threshold`: The maximum allowable distance.
-    fn new(target: K, threshold: Option<D>) -> Self {
+    pub fn new(target: K, threshold: Option<D>) -> Self {
         Self {
             target,
             threshold,
@@ -217,7 +282,7 @@ impl<K, V, D> SingletonNeighborhood<K, V, D> {
     }
 
     /// Convert this result into an optional neighbor.
-    fn into_option(self) -> Option<Neighbor<V, D>> {
+    pub fn into_option(self) -> Option<Neighbor<V, D>> {
         self.neighbor
     }
 }
@@ -247,11 +312,30 @@ where
 
         distance
     }
+
+    fn consider_bounded(&mut self, item: V) {
+        let distance = match self.threshold {
+            Some(t) => self.target.distance_bounded(&item, t),
+            None => Some(self.target.distance(&item)),
+        };
+
+        if let Some(distance) = distance {
+            self.threshold = Some(distance);
+            self.neighbor = Some(Neighbor::new(item, distance));
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(1)
+    }
 }
 
 /// A [Neighborhood] of up to `k` results, using a binary heap.
+///
+/// The results are accumulated into a borrowed vector, which should be [sorted](Self::sort) once
+/// the search is complete.
 #[derive(Debug)]
-struct HeapNeighborhood<'a, K, V, D> {
+pub struct HeapNeighborhood<'a, K, V, D> {
     /// The target of the nearest neighbor search.
     target: K,
     /// The number of nearest neighbors to find.
@@ -268,8 +352,9 @@ impl<'a, K, V, D: Distance> HeapNeighborhood<'a, K, V, D> {
     /// * `target`: The search target.
     /// * `k`: The maximum number of nearest neighbors to find.
     /// * `threshold`: The maximum allowable distance.
-    /// * `heap`: The vector of neighbors to use as the heap.
-    fn new(
+    /// * `heap`: The vector of neighbors to use as the heap.  Its existing contents must be sorted
+    ///   from nearest to farthest.
+    pub fn new(
         target: K,
         k: usize,
         mut threshold: Option<D>,
@@ -336,8 +421,21 @@ impl<'a, K, V, D: Distance> HeapNeighborhood<'a, K, V, D> {
         self.sink_root(self.heap.len());
     }
 
+    /// Insert a neighbor within the threshold, evicting the farthest one if the heap is full.
+    fn insert(&mut self, neighbor: Neighbor<V, D>) {
+        if self.heap.len() < self.k {
+            self.push(neighbor);
+        } else {
+            self.replace_root(neighbor);
+        }
+
+        if self.heap.len() == self.k {
+            self.threshold = Some(self.heap[0].distance);
+        }
+    }
+
     /// Sort the heap from smallest to largest distance.
-    fn sort(&mut self) {
+    pub fn sort(&mut self) {
         for i in (0..self.heap.len()).rev() {
             self.heap.swap(0, i);
             self.sink_root(i);
@@ -364,21 +462,570 @@ where
         let distance = self.target.distance(&item);
 
         if self.contains(distance) {
-            let neighbor = Neighbor::new(item, distance);
+            self.insert(Neighbor::new(item, distance));
+        }
 
-            if self.heap.len() < self.k {
-                self.push(neighbor);
-            } else {
-                self.replace_root(neighbor);
-            }
+        distance
+    }
 
-            if self.heap.len() == self.k {
-                self.threshold = Some(self.heap[0].distance);
-            }
+    fn consider_bounded(&mut self, item: V) {
+        if self.k == 0 {
+            return;
+        }
+
+        let distance = match self.threshold {
+            Some(t) => self.target.distance_bounded(&item, t),
+            None => Some(self.target.distance(&item)),
+        };
+
+        if let Some(distance) = distance {
+            self.insert(Neighbor::new(item, distance));
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.k)
+    }
+}
+
+/// A [Neighborhood] of every result within a fixed radius.
+///
+/// The results are appended to a borrowed vector, which can be [sorted](Self::sort) once the
+/// search is complete.
+#[derive(Debug)]
+pub struct RadiusNeighborhood<'a, K, V, D> {
+    /// The target of the nearest neighbor search.
+    target: K,
+    /// The search radius, which never shrinks.
+    radius: D,
+    /// The neighbors found so far.
+    neighbors: &'a mut Vec<Neighbor<V, D>>,
+}
+
+impl<'a, K, V, D: Distance> RadiusNeighborhood<'a, K, V, D> {
+    /// Create a new RadiusNeighborhood.
+    ///
+    /// * `target`: The search target.
+    /// * `radius`: The maximum allowable distance.
+    /// * `neighbors`: The vector to append neighbors to.
+    pub fn new(target: K, radius: D, neighbors: &'a mut Vec<Neighbor<V, D>>) -> Self {
+        Self {
+            target,
+            radius,
+            neighbors,
+        }
+    }
+
+    /// Sort the neighbors from smallest to largest distance.
+    pub fn sort(&mut self) {
+        // A stable sort, so an already sorted prefix is just merged with the new neighbors
+        self.neighbors.sort_by_key(|n| Ordered::new(n.distance));
+    }
+}
+
+impl<'a, K, V> Neighborhood<K, V> for RadiusNeighborhood<'a, K, V, K::Distance>
+where
+    K: Copy + Proximity<V>,
+{
+    fn target(&self) -> K {
+        self.target
+    }
+
+    fn contains<D>(&self, distance: D) -> bool
+    where
+        D: PartialOrd<K::Distance>,
+    {
+        distance <= self.radius
+    }
+
+    fn consider(&mut self, item: V) -> K::Distance {
+        let distance = self.target.distance(&item);
+
+        if self.contains(distance) {
+            self.neighbors.push(Neighbor::new(item, distance));
         }
 
         distance
     }
+
+    fn consider_bounded(&mut self, item: V) {
+        if let Some(distance) = self.target.distance_bounded(&item, self.radius) {
+            self.neighbors.push(Neighbor::new(item, distance));
+        }
+    }
+}
+
+/// A [Neighborhood] wrapper that only accepts items which satisfy a predicate.
+///
+/// Rejected items are still measured, so searches can use their distances for pruning, but they
+/// never shrink the search radius.
+///
+///     use acap::euclid::Euclidean;
+///     use acap::vp::VpTree;
+///     use acap::{FilterNeighborhood, HeapNeighborhood, NearestNeighbors};
+///
+///     let tree = VpTree::balanced(vec![
+///         Euclidean([3, 4]),
+///         Euclidean([5, 12]),
+///         Euclidean([8, 15]),
+///         Euclidean([7, 24]),
+///     ]);
+///
+///     let target = Euclidean([7, 7]);
+///     let mut neighbors = Vec::new();
+///     let odd = |e: &&Euclidean<[i32; 2]>| e.0[0] % 2 == 1;
+///     let heap = HeapNeighborhood::new(&target, 2, None, &mut neighbors);
+///     tree.search(FilterNeighborhood::new(heap, odd))
+///         .into_inner()
+///         .sort();
+///
+///     assert_eq!(neighbors[0].item, &Euclidean([3, 4]));
+///     assert_eq!(neighbors[1].item, &Euclidean([5, 12]));
+#[derive(Debug)]
+pub struct FilterNeighborhood<N, F> {
+    /// The wrapped neighborhood.
+    inner: N,
+    /// The predicate that items must satisfy.
+    predicate: F,
+}
+
+impl<N, F> FilterNeighborhood<N, F> {
+    /// Create a new FilterNeighborhood.
+    ///
+    /// * `inner`: The neighborhood to wrap.
+    /// * `predicate`: The predicate that items must satisfy.
+    pub fn new(inner: N, predicate: F) -> Self {
+        Self { inner, predicate }
+    }
+
+    /// Unwrap the inner neighborhood.
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+impl<K, V, N, F> Neighborhood<K, V> for FilterNeighborhood<N, F>
+where
+    K: Proximity<V>,
+    N: Neighborhood<K, V>,
+    F: FnMut(&V) -> bool,
+{
+    fn target(&self) -> K {
+        self.inner.target()
+    }
+
+    fn contains<D>(&self, distance: D) -> bool
+    where
+        D: PartialOrd<K::Distance>,
+    {
+        self.inner.contains(distance)
+    }
+
+    fn consider(&mut self, item: V) -> K::Distance {
+        if (self.predicate)(&item) {
+            self.inner.consider(item)
+        } else {
+            self.target().distance(&item)
+        }
+    }
+
+    fn consider_bounded(&mut self, item: V) {
+        if (self.predicate)(&item) {
+            self.inner.consider_bounded(item);
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.inner.is_exhausted()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    fn visit(&mut self, depth: usize) {
+        self.inner.visit(depth);
+    }
+
+    fn prune(&mut self, depth: usize) {
+        self.inner.prune(depth);
+    }
+}
+
+/// A [Neighborhood] wrapper that skips the search target itself.
+///
+/// This is useful for searching for the neighbors of an item that is in the index.  Items are
+/// compared by equality, so duplicates of the target are skipped as well.
+#[derive(Debug)]
+pub struct ExcludeNeighborhood<N> {
+    /// The wrapped neighborhood.
+    inner: N,
+}
+
+impl<N> ExcludeNeighborhood<N> {
+    /// Create a new ExcludeNeighborhood.
+    pub fn new(inner: N) -> Self {
+        Self { inner }
+    }
+
+    /// Unwrap the inner neighborhood.
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+impl<K, V, N> Neighborhood<K, V> for ExcludeNeighborhood<N>
+where
+    K: Proximity<V> + PartialEq<V>,
+    N: Neighborhood<K, V>,
+{
+    fn target(&self) -> K {
+        self.inner.target()
+    }
+
+    fn contains<D>(&self, distance: D) -> bool
+    where
+        D: PartialOrd<K::Distance>,
+    {
+        self.inner.contains(distance)
+    }
+
+    fn consider(&mut self, item: V) -> K::Distance {
+        let target = self.target();
+        if target == item {
+            target.distance(&item)
+        } else {
+            self.inner.consider(item)
+        }
+    }
+
+    fn consider_bounded(&mut self, item: V) {
+        if self.target() != item {
+            self.inner.consider_bounded(item);
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.inner.is_exhausted()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        // Leave room for the target itself, which will be skipped
+        self.inner.capacity().map(|c| c.saturating_add(1))
+    }
+
+    fn visit(&mut self, depth: usize) {
+        self.inner.visit(depth);
+    }
+
+    fn prune(&mut self, depth: usize) {
+        self.inner.prune(depth);
+    }
+}
+
+/// A [Neighborhood] wrapper that maps items before passing them along.
+///
+/// The target must be comparable to both the original and the mapped items, with the same
+/// distances.
+#[derive(Debug)]
+pub struct MapNeighborhood<N, F> {
+    /// The wrapped neighborhood.
+    inner: N,
+    /// The function to apply to each item.
+    f: F,
+}
+
+impl<N, F> MapNeighborhood<N, F> {
+    /// Create a new MapNeighborhood.
+    ///
+    /// * `inner`: The neighborhood to wrap.
+    /// * `f`: The function to apply to each item.
+    pub fn new(inner: N, f: F) -> Self {
+        Self { inner, f }
+    }
+
+    /// Unwrap the inner neighborhood.
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+impl<K, V, W, N, F> Neighborhood<K, V> for MapNeighborhood<N, F>
+where
+    K: Proximity<V> + Proximity<W, Distance = <K as Proximity<V>>::Distance>,
+    N: Neighborhood<K, W>,
+    F: FnMut(V) -> W,
+{
+    fn target(&self) -> K {
+        self.inner.target()
+    }
+
+    fn contains<D>(&self, distance: D) -> bool
+    where
+        D: PartialOrd<<K as Proximity<V>>::Distance>,
+    {
+        self.inner.contains(distance)
+    }
+
+    fn consider(&mut self, item: V) -> <K as Proximity<V>>::Distance {
+        self.inner.consider((self.f)(item))
+    }
+
+    fn consider_bounded(&mut self, item: V) {
+        self.inner.consider_bounded((self.f)(item));
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.inner.is_exhausted()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    fn visit(&mut self, depth: usize) {
+        self.inner.visit(depth);
+    }
+
+    fn prune(&mut self, depth: usize) {
+        self.inner.prune(depth);
+    }
+}
+
+/// A [Neighborhood] wrapper that limits the number of candidates considered by a search.
+///
+/// Every candidate costs one distance evaluation.  Once the budget is spent, the neighborhood is
+/// [exhausted](Neighborhood::is_exhausted) and no longer [contains](Neighborhood::contains) any
+/// distance, so the search stops early with the best results found so far.
+///
+///     use acap::euclid::Euclidean;
+///     use acap::vp::VpTree;
+///     use acap::{BudgetNeighborhood, HeapNeighborhood, NearestNeighbors};
+///
+///     let tree = VpTree::balanced(vec![
+///         Euclidean([3, 4]),
+///         Euclidean([5, 12]),
+///         Euclidean([8, 15]),
+///         Euclidean([7, 24]),
+///     ]);
+///
+///     let target = Euclidean([7, 7]);
+///     let mut neighbors = Vec::new();
+///     let heap = HeapNeighborhood::new(&target, 3, None, &mut neighbors);
+///     let budget = tree.search(BudgetNeighborhood::new(heap, 2));
+///     assert!(budget.is_truncated());
+///
+///     budget.into_inner().sort();
+///     assert!(neighbors.len() <= 2);
+#[derive(Debug)]
+pub struct BudgetNeighborhood<N> {
+    /// The wrapped neighborhood.
+    inner: N,
+    /// The number of candidates left to consider.
+    remaining: usize,
+    /// Whether the search was cut short by the budget.
+    truncated: Cell<bool>,
+}
+
+impl<N> BudgetNeighborhood<N> {
+    /// Create a new BudgetNeighborhood.
+    ///
+    /// * `inner`: The neighborhood to wrap.
+    /// * `budget`: The maximum number of candidates to consider.
+    pub fn new(inner: N, budget: usize) -> Self {
+        Self {
+            inner,
+            remaining: budget,
+            truncated: Cell::new(false),
+        }
+    }
+
+    /// Get the number of candidates that can still be considered.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Check whether the search was cut short by the budget, possibly missing some results.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.get()
+    }
+
+    /// Unwrap the inner neighborhood.
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+impl<K, V, N> Neighborhood<K, V> for BudgetNeighborhood<N>
+where
+    K: Proximity<V>,
+    N: Neighborhood<K, V>,
+{
+    fn target(&self) -> K {
+        self.inner.target()
+    }
+
+    fn contains<D>(&self, distance: D) -> bool
+    where
+        D: PartialOrd<K::Distance>,
+    {
+        let contains = self.inner.contains(distance);
+        if contains && self.remaining == 0 {
+            self.truncated.set(true);
+            false
+        } else {
+            contains
+        }
+    }
+
+    fn consider(&mut self, item: V) -> K::Distance {
+        if self.remaining == 0 {
+            // The search ignored is_exhausted(), so just measure the item without keeping it
+            self.truncated.set(true);
+            self.target().distance(&item)
+        } else {
+            self.remaining -= 1;
+            self.inner.consider(item)
+        }
+    }
+
+    fn consider_bounded(&mut self, item: V) {
+        if self.remaining == 0 {
+            self.truncated.set(true);
+        } else {
+            self.remaining -= 1;
+            self.inner.consider_bounded(item);
+        }
+    }
+
+    fn is_exhausted(&self) -> bool {
+        if self.remaining == 0 {
+            self.truncated.set(true);
+            true
+        } else {
+            self.inner.is_exhausted()
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    fn visit(&mut self, depth: usize) {
+        self.inner.visit(depth);
+    }
+
+    fn prune(&mut self, depth: usize) {
+        self.inner.prune(depth);
+    }
+}
+
+/// Statistics about a single nearest neighbor search.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SearchStats {
+    /// The number of distances evaluated, i.e. candidates considered.
+    pub distance_evals: usize,
+    /// The number of index nodes visited.
+    pub nodes_visited: usize,
+    /// The number of subtrees pruned.
+    pub subtrees_pruned: usize,
+    /// The maximum depth of any visited node, with the root at depth zero.
+    pub max_depth: usize,
+}
+
+/// A [Neighborhood] wrapper that collects [statistics](SearchStats) about a search.
+///
+/// Distance evaluations are counted for every index, but node visits and pruned subtrees are only
+/// reported by tree-based indexes like [VP trees](vp) and [k-d trees](kd).
+///
+///     use acap::euclid::Euclidean;
+///     use acap::vp::FlatVpTree;
+///     use acap::{SingletonNeighborhood, StatsNeighborhood, NearestNeighbors};
+///
+///     let tree = FlatVpTree::balanced(vec![
+///         Euclidean([3, 4]),
+///         Euclidean([5, 12]),
+///         Euclidean([8, 15]),
+///         Euclidean([7, 24]),
+///     ]);
+///
+///     let target = Euclidean([7, 7]);
+///     let singleton = SingletonNeighborhood::new(&target, None);
+///     let stats = tree.search(StatsNeighborhood::new(singleton)).stats();
+///     assert!(stats.distance_evals <= 4);
+///     assert_eq!(stats.distance_evals, stats.nodes_visited);
+#[derive(Debug)]
+pub struct StatsNeighborhood<N> {
+    /// The wrapped neighborhood.
+    inner: N,
+    /// The statistics collected so far.
+    stats: SearchStats,
+}
+
+impl<N> StatsNeighborhood<N> {
+    /// Create a new StatsNeighborhood.
+    pub fn new(inner: N) -> Self {
+        Self {
+            inner,
+            stats: SearchStats::default(),
+        }
+    }
+
+    /// Get the statistics collected so far.
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Unwrap the inner neighborhood.
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+}
+
+impl<K, V, N> Neighborhood<K, V> for StatsNeighborhood<N>
+where
+    K: Proximity<V>,
+    N: Neighborhood<K, V>,
+{
+    fn target(&self) -> K {
+        self.inner.target()
+    }
+
+    fn contains<D>(&self, distance: D) -> bool
+    where
+        D: PartialOrd<K::Distance>,
+    {
+        self.inner.contains(distance)
+    }
+
+    fn consider(&mut self, item: V) -> K::Distance {
+        self.stats.distance_evals += 1;
+        self.inner.consider(item)
+    }
+
+    fn consider_bounded(&mut self, item: V) {
+        self.stats.distance_evals += 1;
+        self.inner.consider_bounded(item);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.inner.is_exhausted()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.inner.capacity()
+    }
+
+    fn visit(&mut self, depth: usize) {
+        self.stats.nodes_visited += 1;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        self.inner.visit(depth);
+    }
+
+    fn prune(&mut self, depth: usize) {
+        self.stats.subtrees_pruned += 1;
+        self.inner.prune(depth);
+    }
 }
 
 /// A [nearest neighbor search] index.
@@ -468,6 +1115,83 @@ pub trait NearestNeighbors<K: Proximity<V>, V = K> {
         }
     }
 
+    /// Returns the nearest neighbor to `target` that satisfies a `predicate`, if one exists.
+    ///
+    /// Items that don't satisfy the predicate are skipped without shrinking the search radius.
+    fn nearest_filtered<F>(
+        &self,
+        target: &K,
+        mut predicate: F,
+    ) -> Option<Neighbor<&V, K::Distance>>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        let singleton = SingletonNeighborhood::new(target, None);
+        self.search(FilterNeighborhood::new(singleton, |v: &&V| predicate(v)))
+            .into_inner()
+            .into_option()
+    }
+
+    /// Returns the up to `k` nearest neighbors to `target` that satisfy a `predicate`.
+    ///
+    /// Items that don't satisfy the predicate are skipped without shrinking the search radius.  The
+    /// result will be sorted from nearest to farthest.
+    fn k_nearest_filtered<F>(
+        &self,
+        target: &K,
+        k: usize,
+        mut predicate: F,
+    ) -> Vec<Neighbor<&V, K::Distance>>
+    where
+        F: FnMut(&V) -> bool,
+    {
+        let mut neighbors = Vec::with_capacity(k);
+        let heap = HeapNeighborhood::new(target, k, None, &mut neighbors);
+        self.search(FilterNeighborhood::new(heap, |v: &&V| predicate(v)))
+            .into_inner()
+            .sort();
+        neighbors
+    }
+
+    /// Returns every neighbor of `target` within the distance `radius`.
+    ///
+    /// The result will be sorted from nearest to farthest.
+    fn within<D>(&self, target: &K, radius: D) -> Vec<Neighbor<&V, K::Distance>>
+    where
+        D: TryInto<K::Distance>,
+    {
+        let mut neighbors = Vec::new();
+        self.merge_within(target, radius, &mut neighbors);
+        neighbors
+    }
+
+    /// Returns every neighbor of `target` within the distance `radius`, in no particular order.
+    fn within_unsorted<D>(&self, target: &K, radius: D) -> Vec<Neighbor<&V, K::Distance>>
+    where
+        D: TryInto<K::Distance>,
+    {
+        let mut neighbors = Vec::new();
+        if let Ok(distance) = radius.try_into() {
+            self.search(RadiusNeighborhood::new(target, distance, &mut neighbors));
+        }
+        neighbors
+    }
+
+    /// Merges every neighbor within the distance `radius` into an existing sorted vector.
+    fn merge_within<'v, D>(
+        &'v self,
+        target: &K,
+        radius: D,
+        neighbors: &mut Vec<Neighbor<&'v V, K::Distance>>,
+    ) where
+        D: TryInto<K::Distance>,
+    {
+        if let Ok(distance) = radius.try_into() {
+            self.search(RadiusNeighborhood::new(target, distance, neighbors))
+                .sort();
+        }
+    }
+
     /// Search for nearest neighbors and add them to a neighborhood.
     fn search<'k, 'v, N>(&'v self, neighborhood: N) -> N
     where
@@ -484,6 +1208,7 @@ pub mod tests {
     use super::*;
 
     use crate::exhaustive::ExhaustiveSearch;
+    use crate::vp::VpTree;
 
     use rand::prelude::*;
 
@@ -491,15 +1216,119 @@ pub mod tests {
 
     type Point = Euclidean<[f32; 3]>;
 
+    #[test]
+    fn test_neighborhood_wrappers() {
+        let points: Vec<Point> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let index = VpTree::from_iter(points.clone());
+        let target = points[0];
+
+        let mut neighbors = Vec::new();
+        let heap = HeapNeighborhood::new(&target, 3, None, &mut neighbors);
+        index.search(ExcludeNeighborhood::new(heap))
+            .into_inner()
+            .sort();
+        assert_eq!(neighbors, index.k_nearest(&target, 4)[1..]);
+
+        // ExhaustiveSearch only considers items with bounded distances
+        let exhaustive = ExhaustiveSearch::from_iter(points.clone());
+        let mut bounded = Vec::new();
+        let heap = HeapNeighborhood::new(&target, 3, None, &mut bounded);
+        exhaustive.search(ExcludeNeighborhood::new(heap))
+            .into_inner()
+            .sort();
+        assert_eq!(bounded, neighbors);
+
+        let predicate = |p: &&Point| p.0[0] < 0.5;
+        let eindex: ExhaustiveSearch<_> = points.iter().filter(predicate).copied().collect();
+        let mut neighbors = Vec::new();
+        let heap = HeapNeighborhood::new(&target, 3, None, &mut neighbors);
+        index.search(FilterNeighborhood::new(heap, predicate))
+            .into_inner()
+            .sort();
+        assert_eq!(neighbors, eindex.k_nearest(&target, 3));
+
+        fn unwrap(p: &Point) -> &[f32; 3] {
+            &p.0
+        }
+
+        let singleton = SingletonNeighborhood::new(&target, None);
+        let nearest = index.search(MapNeighborhood::new(singleton, unwrap))
+            .into_inner()
+            .into_option()
+            .expect("No nearest neighbor found");
+        assert_eq!(nearest.item, &target.0);
+        assert_eq!(nearest.distance, 0.0);
+    }
+
     /// Test an [ExactNeighbors] implementation.
     pub fn test_exact_neighbors<T, F>(from_iter: F)
     where
         T: ExactNeighbors<Point>,
         F: Fn(Vec<Point>) -> T,
+    {
+        test_nearest_neighbors(from_iter);
+    }
+
+    /// Make a copy of some points with shuffled coordinates, to be removed from an index.
+    fn shuffled(points: &[Point]) -> Vec<Point> {
+        points
+            .iter()
+            .map(|p| Euclidean([p.0[1], p.0[2], p.0[0] + 1.0]))
+            .collect()
+    }
+
+    /// Test removing items from an [ExactNeighbors] implementation.
+    pub fn test_remove<T, F, R, L>(from_iter: F, remove: R, len: L)
+    where
+        T: ExactNeighbors<Point>,
+        F: Fn(Vec<Point>) -> T,
+        R: Fn(&mut T, &Point) -> Option<Point>,
+        L: Fn(&T) -> usize,
+    {
+        test_exact_neighbors(|points| {
+            let extra = shuffled(&points);
+            let mut index = from_iter(extra.iter().chain(&points).copied().collect());
+            for item in &extra {
+                assert_eq!(remove(&mut index, item).as_ref(), Some(item));
+            }
+            for item in &extra {
+                assert_eq!(remove(&mut index, item), None);
+            }
+            assert_eq!(len(&index), points.len());
+            index
+        });
+    }
+
+    /// Test retaining items in an [ExactNeighbors] implementation.
+    pub fn test_retain<T, F, R, L>(from_iter: F, retain: R, len: L)
+    where
+        T: ExactNeighbors<Point>,
+        F: Fn(Vec<Point>) -> T,
+        R: Fn(&mut T, &mut dyn FnMut(&Point) -> bool),
+        L: Fn(&T) -> usize,
+    {
+        test_exact_neighbors(|points| {
+            let extra = shuffled(&points);
+            let items = points.iter().zip(&extra).flat_map(|(p, e)| vec![*p, *e]);
+            let mut index = from_iter(items.collect());
+            retain(&mut index, &mut |item| !extra.contains(item));
+            assert_eq!(len(&index), points.len());
+            index
+        });
+    }
+
+    /// Test a [NearestNeighbors] implementation, configured to give exact results.
+    pub fn test_nearest_neighbors<T, F>(from_iter: F)
+    where
+        T: NearestNeighbors<Point>,
+        F: Fn(Vec<Point>) -> T,
     {
         test_empty(&from_iter);
         test_pythagorean(&from_iter);
         test_random_points(&from_iter);
+        test_budget(&from_iter);
     }
 
     fn test_empty<T, F>(from_iter: &F)
@@ -516,6 +1345,8 @@ pub mod tests {
         assert!(index.k_nearest(&target, 3).is_empty());
         assert!(index.k_nearest_within(&target, 0, 1.0).is_empty());
         assert!(index.k_nearest_within(&target, 3, 1.0).is_empty());
+        assert!(index.within(&target, 1.0).is_empty());
+        assert!(index.within_unsorted(&target, 1.0).is_empty());
     }
 
     fn test_pythagorean<T, F>(from_iter: &F)
@@ -597,6 +1428,56 @@ pub mod tests {
                 Neighbor::new(&Euclidean([3.0, 4.0, 0.0]), 5.0),
             ]
         );
+
+        assert_eq!(
+            index.nearest_filtered(&target, |p| p.0[2] == 0.0).expect("No nearest neighbor found"),
+            Neighbor::new(&Euclidean([3.0, 4.0, 0.0]), 5.0)
+        );
+        assert_eq!(index.nearest_filtered(&target, |_| false), None);
+        assert_eq!(
+            index.k_nearest_filtered(&target, 2, |p| p.0[0] >= 3.0),
+            vec![
+                Neighbor::new(&Euclidean([3.0, 4.0, 0.0]), 5.0),
+                Neighbor::new(&Euclidean([4.0, 4.0, 7.0]), 9.0),
+            ]
+        );
+
+        assert!(index.within(&target, 2.0).is_empty());
+        assert_eq!(
+            index.within(&target, 7.0),
+            vec![
+                Neighbor::new(&Euclidean([1.0, 2.0, 2.0]), 3.0),
+                Neighbor::new(&Euclidean([3.0, 4.0, 0.0]), 5.0),
+                Neighbor::new(&Euclidean([2.0, 3.0, 6.0]), 7.0),
+            ]
+        );
+
+        let mut unsorted = index.within_unsorted(&target, 10.0);
+        unsorted.sort_by_key(|n| Ordered::new(n.distance));
+        assert_eq!(
+            unsorted,
+            vec![
+                Neighbor::new(&Euclidean([1.0, 2.0, 2.0]), 3.0),
+                Neighbor::new(&Euclidean([3.0, 4.0, 0.0]), 5.0),
+                Neighbor::new(&Euclidean([2.0, 3.0, 6.0]), 7.0),
+                Neighbor::new(&Euclidean([4.0, 4.0, 7.0]), 9.0),
+            ]
+        );
+
+        neighbors = vec![
+            Neighbor::new(&target, EuclideanDistance::from_squared(0.0)),
+            Neighbor::new(&Euclidean([0.0, 0.0, 6.0]), EuclideanDistance::from_squared(36.0)),
+        ];
+        index.merge_within(&target, 5.0, &mut neighbors);
+        assert_eq!(
+            neighbors,
+            vec![
+                Neighbor::new(&target, 0.0),
+                Neighbor::new(&Euclidean([1.0, 2.0, 2.0]), 3.0),
+                Neighbor::new(&Euclidean([3.0, 4.0, 0.0]), 5.0),
+                Neighbor::new(&Euclidean([0.0, 0.0, 6.0]), 6.0),
+            ]
+        );
     }
 
     fn test_random_points<T, F>(from_iter: &F)
@@ -621,5 +1502,72 @@ pub mod tests {
             target,
             points,
         );
+
+        let predicate = |p: &Point| p.0[0] < 0.5;
+        assert_eq!(
+            index.nearest_filtered(&target, predicate),
+            eindex.nearest_filtered(&target, predicate),
+            "target: {:?}, points: {:#?}",
+            target,
+            points,
+        );
+        assert_eq!(
+            index.k_nearest_filtered(&target, 3, predicate),
+            eindex.k_nearest_filtered(&target, 3, predicate),
+            "target: {:?}, points: {:#?}",
+            target,
+            points,
+        );
+
+        let filtered: ExhaustiveSearch<_> = points.iter().copied().filter(predicate).collect();
+        assert_eq!(
+            eindex.k_nearest_filtered(&target, 3, predicate),
+            filtered.k_nearest(&target, 3),
+        );
+
+        assert_eq!(
+            index.within(&target, 0.25),
+            eindex.within(&target, 0.25),
+            "target: {:?}, points: {:#?}",
+            target,
+            points,
+        );
+    }
+
+    fn test_budget<T, F>(from_iter: &F)
+    where
+        T: NearestNeighbors<Point>,
+        F: Fn(Vec<Point>) -> T,
+    {
+        let mut points = Vec::new();
+        for _ in 0..256 {
+            points.push(Euclidean([random(), random(), random()]));
+        }
+
+        let index = from_iter(points);
+        let target = Euclidean([random(), random(), random()]);
+
+        // Count every candidate the index considers, including those over budget
+        let mut count = 0;
+        let mut neighbors = Vec::new();
+        let heap = HeapNeighborhood::new(&target, 3, None, &mut neighbors);
+        let budget = BudgetNeighborhood::new(heap, 2);
+        let counter = FilterNeighborhood::new(budget, |_: &&Point| {
+            count += 1;
+            true
+        });
+        let budget = index.search(counter).into_inner();
+        assert!(budget.is_truncated());
+        assert_eq!(budget.remaining(), 0);
+        budget.into_inner().sort();
+        assert_eq!(count, 2);
+        assert_eq!(neighbors.len(), 2);
+
+        let mut neighbors = Vec::new();
+        let heap = HeapNeighborhood::new(&target, 3, None, &mut neighbors);
+        let budget = index.search(BudgetNeighborhood::new(heap, usize::MAX));
+        assert!(!budget.is_truncated());
+        budget.into_inner().sort();
+        assert_eq!(neighbors, index.k_nearest(&target, 3));
     }
 }
@@ -4,10 +4,17 @@
 //! [Minkowski]: https://en.wikipedia.org/wiki/Minkowski_distance
 
 use crate::coords::Coordinates;
-use crate::distance::Proximity;
+use crate::distance::{Distance, Metric, Proximity, Value};
+use crate::euclid::NegativeDistanceError;
 
 use num_traits::real::Real;
-use num_traits::zero;
+use num_traits::{one, zero, Signed};
+
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 
 /// A point in L<sup>1</sup> space.
 pub use crate::taxi::Taxicab as L1;
@@ -56,6 +63,37 @@ where
     sum.powf(p.recip())
 }
 
+/// Compute the [`$\ell^p$`]/[Minkowski] distance between two points, if it is at most `bound`.
+///
+/// The sum of powers is checked against `$\mathrm{bound}^p$` as it goes, so points that are far
+/// apart can be rejected without looking at all of their coordinates.
+///
+/// [`$\ell^p$`]: https://en.wikipedia.org/wiki/Lp_space
+/// [Minkowski]: https://en.wikipedia.org/wiki/Minkowski_distance
+pub fn bounded_lp_distance<T, U>(p: T::Value, x: T, y: U, bound: T::Value) -> Option<T::Value>
+where
+    T: Coordinates,
+    U: Coordinates<Value = T::Value>,
+    T::Value: Real,
+{
+    debug_assert!(x.dims() == y.dims());
+
+    if bound < zero() {
+        return None;
+    }
+
+    let limit = bound.powf(p);
+    let mut sum: T::Value = zero();
+    for i in 0..x.dims() {
+        sum += (x.coord(i) - y.coord(i)).abs().powf(p);
+        if sum > limit {
+            return None;
+        }
+    }
+
+    Some(sum.powf(p.recip())).filter(|&d| d <= bound)
+}
+
 /// Marker trait for [Minkowski distances].
 ///
 /// [Minkowski distances]: https://en.wikipedia.org/wiki/Minkowski_distance
@@ -64,10 +102,430 @@ pub trait Minkowski<T: ?Sized = Self>: Proximity<T> {}
 /// Blanket [`Minkowski`] implementation for references.
 impl<'k, 'v, K: Minkowski<V>, V> Minkowski<&'v V> for &'k K {}
 
+/// The exponent `$p$` of an [`Lp`] space.
+///
+/// This is implemented by plain floating-point numbers, for exponents chosen at runtime, and by
+/// [`ConstP`], for exponents known at compile time.
+pub trait Exponent<T>: Copy {
+    /// Get the value of `$p$`.
+    fn p(self) -> T;
+
+    /// Raise a non-negative number to the power `$p$`.
+    fn pow(self, x: T) -> T;
+
+    /// Take the `$p$`th root of a non-negative number.
+    fn root(self, x: T) -> T;
+}
+
+/// An integer exponent `$p$` known at compile time.
+///
+/// Unlike a runtime exponent, this takes up no space in every [`Lp`] point, and powers are
+/// computed by repeated multiplication.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ConstP<const P: u32>;
+
+/// A point in [`$\ell^p$`] space.
+///
+/// This wrapper equips any [coordinate space] with the [Minkowski distance] of order `$p$`, which
+/// can be chosen at runtime or at compile time (see [`Exponent`]).  Points that are compared with
+/// each other must all have the same `$p$`, since the distance between points with different
+/// exponents isn't well-defined (this is only checked in debug builds).
+///
+///     use acap::distance::{Distance, Proximity};
+///     use acap::lp::{ConstP, Lp};
+///
+///     let x = Lp::new(3.0, [0.0, 0.0]);
+///     let y = Lp::new(3.0, [3.0, 4.0]);
+///     assert!(x.distance(&y) < 5.0);
+///
+///     let x = Lp::new(ConstP::<1>, [0.0, 0.0]);
+///     let y = Lp::new(ConstP::<1>, [3.0, 4.0]);
+///     assert_eq!(x.distance(&y).value(), 7.0);
+///
+/// [`$\ell^p$`]: https://en.wikipedia.org/wiki/Lp_space
+/// [coordinate space]: Coordinates
+/// [Minkowski distance]: lp_distance
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Lp<T, P> {
+    /// The exponent.
+    p: P,
+    /// The wrapped point.
+    point: T,
+}
+
+/// The serialized form of an [`Lp`] point, before its exponent is checked.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(rename = "Lp")]
+struct LpData<T, P> {
+    p: P,
+    point: T,
+}
+
+impl<T: Coordinates, P: Exponent<T::Value>> Lp<T, P> {
+    /// Wrap a point.
+    ///
+    /// # Panics
+    ///
+    /// If `$p < 1$`, since the Minkowski distance is only a metric for `$p \ge 1$`.
+    pub fn new(p: P, point: T) -> Self {
+        assert!(p.p() >= one(), "Lp spaces require p >= 1");
+        Self { p, point }
+    }
+}
+
+/// Deserializes the point after checking that `$p \ge 1$`.
+#[cfg(feature = "serde")]
+impl<'de, T, P> Deserialize<'de> for Lp<T, P>
+where
+    T: Coordinates + Deserialize<'de>,
+    P: Exponent<T::Value> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let LpData { p, point }: LpData<T, P> = LpData::deserialize(deserializer)?;
+        if p.p() >= one() {
+            Ok(Self { p, point })
+        } else {
+            Err(de::Error::custom("Lp spaces require p >= 1"))
+        }
+    }
+}
+
+impl<T, P: Copy> Lp<T, P> {
+    /// Get the exponent `$p$`.
+    pub fn p(&self) -> P {
+        self.p
+    }
+
+    /// Unwrap a point.
+    pub fn inner(&self) -> &T {
+        &self.point
+    }
+
+    /// Unwrap a point.
+    pub fn into_inner(self) -> T {
+        self.point
+    }
+}
+
+impl<T: Coordinates, P> Coordinates for Lp<T, P> {
+    type Value = T::Value;
+
+    fn dims(&self) -> usize {
+        self.point.dims()
+    }
+
+    fn coord(&self, i: usize) -> Self::Value {
+        self.point.coord(i)
+    }
+}
+
+/// Compute the sum of the `$p$`th powers of the coordinate differences, if it is at most `limit`.
+fn power_sum<T, U, P>(p: P, x: T, y: U, limit: Option<T::Value>) -> Option<T::Value>
+where
+    T: Coordinates,
+    U: Coordinates<Value = T::Value>,
+    P: Exponent<T::Value>,
+{
+    debug_assert!(x.dims() == y.dims());
+
+    let mut sum = zero();
+    for i in 0..x.dims() {
+        sum += p.pow(Signed::abs(&(x.coord(i) - y.coord(i))));
+        if let Some(l) = limit {
+            if sum > l {
+                return None;
+            }
+        }
+    }
+
+    match limit {
+        Some(l) if sum <= l => Some(sum),
+        Some(_) => None,
+        None => Some(sum),
+    }
+}
+
+/// The Minkowski distance function.
+impl<T, P> Proximity for Lp<T, P>
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{
+    type Distance = LpDistance<T::Value, P>;
+
+    fn distance(&self, other: &Self) -> Self::Distance {
+        debug_assert!(self.p.p() == other.p.p(), "Lp points have different exponents");
+        self.distance(&other.point)
+    }
+
+    fn distance_bounded(&self, other: &Self, bound: Self::Distance) -> Option<Self::Distance> {
+        debug_assert!(self.p.p() == other.p.p(), "Lp points have different exponents");
+        self.distance_bounded(&other.point, bound)
+    }
+}
+
+impl<T, P> Proximity<T> for Lp<T, P>
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{
+    type Distance = LpDistance<T::Value, P>;
+
+    fn distance(&self, other: &T) -> Self::Distance {
+        let sum = power_sum(self.p, &self.point, other, None).unwrap();
+        LpDistance::from_powered(self.p, sum)
+    }
+
+    fn distance_bounded(&self, other: &T, bound: Self::Distance) -> Option<Self::Distance> {
+        debug_assert!(self.p.p() == bound.p.p(), "Lp distances have different exponents");
+        power_sum(self.p, &self.point, other, Some(bound.powered_value()))
+            .map(|sum| LpDistance::from_powered(self.p, sum))
+    }
+}
+
+impl<T, P> Proximity<Lp<T, P>> for T
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{
+    type Distance = LpDistance<T::Value, P>;
+
+    fn distance(&self, other: &Lp<T, P>) -> Self::Distance {
+        other.distance(self)
+    }
+
+    fn distance_bounded(&self, other: &Lp<T, P>, bound: Self::Distance) -> Option<Self::Distance> {
+        other.distance_bounded(self, bound)
+    }
+}
+
+/// Minkowski distance is a metric for `$p \ge 1$`.
+impl<T, P> Metric for Lp<T, P>
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{}
+
+impl<T, P> Metric<T> for Lp<T, P>
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{}
+
+impl<T, P> Metric<Lp<T, P>> for T
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{}
+
+/// Minkowski distance is a [Minkowski] distance.
+impl<T, P> Minkowski for Lp<T, P>
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{}
+
+impl<T, P> Minkowski<T> for Lp<T, P>
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{}
+
+impl<T, P> Minkowski<Lp<T, P>> for T
+where
+    T: Coordinates,
+    P: Exponent<T::Value>,
+    LpDistance<T::Value, P>: Distance,
+{}
+
+/// A [Minkowski distance].
+///
+/// Like [`EuclideanDistance`], this type stores the distance raised to the power `$p$`, to avoid
+/// computing expensive roots until absolutely necessary.  It also holds on to `$p$` itself, so it
+/// can be compared with plain numbers.
+///
+///     # use acap::distance::Distance;
+///     # use acap::lp::{ConstP, LpDistance};
+///     let a = LpDistance::from_powered(ConstP::<3>, 8.0);
+///     let b = LpDistance::from_powered(ConstP::<3>, 27.0);
+///     assert!(a < b && a < 3.0 && 2.5 < b);
+///     assert_eq!(b.value(), 3.0);
+///
+/// [Minkowski distance]: lp_distance
+/// [`EuclideanDistance`]: crate::euclid::EuclideanDistance
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct LpDistance<T, P> {
+    /// The exponent.
+    p: P,
+    /// The distance, raised to the power `$p$`.
+    powered: T,
+}
+
+impl<T: Value, P: Exponent<T>> LpDistance<T, P> {
+    /// Creates an `LpDistance` from a value already raised to the power `$p$`.
+    pub fn from_powered(p: P, value: T) -> Self {
+        debug_assert!(!value.is_negative());
+        Self { p, powered: value }
+    }
+
+    /// Creates an `LpDistance` from a non-negative distance value.
+    pub fn from_value(p: P, value: T) -> Self {
+        debug_assert!(!value.is_negative());
+        Self::from_powered(p, p.pow(value))
+    }
+
+    /// Get the distance value raised to the power `$p$`.
+    pub fn powered_value(self) -> T {
+        self.powered
+    }
+}
+
+/// Distances are compared by their powered values, so they must share the same `$p$`.
+impl<T: PartialEq, P: Exponent<T>> PartialEq for LpDistance<T, P> {
+    fn eq(&self, other: &Self) -> bool {
+        debug_assert!(self.p.p() == other.p.p(), "Lp distances have different exponents");
+        self.powered == other.powered
+    }
+}
+
+impl<T: PartialOrd, P: Exponent<T>> PartialOrd for LpDistance<T, P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        debug_assert!(self.p.p() == other.p.p(), "Lp distances have different exponents");
+        self.powered.partial_cmp(&other.powered)
+    }
+}
+
+/// Implement [`Exponent`] and [`LpDistance`] for a floating-point type.
+macro_rules! float_lp {
+    ($f:ty) => {
+        impl Exponent<$f> for $f {
+            #[inline]
+            fn p(self) -> $f {
+                self
+            }
+
+            #[inline]
+            fn pow(self, x: $f) -> $f {
+                x.powf(self)
+            }
+
+            #[inline]
+            fn root(self, x: $f) -> $f {
+                x.powf(self.recip())
+            }
+        }
+
+        impl<const P: u32> Exponent<$f> for ConstP<P> {
+            #[inline]
+            fn p(self) -> $f {
+                P as $f
+            }
+
+            #[inline]
+            fn pow(self, x: $f) -> $f {
+                x.powi(P as i32)
+            }
+
+            #[inline]
+            fn root(self, x: $f) -> $f {
+                match P {
+                    1 => x,
+                    2 => x.sqrt(),
+                    _ => x.powf((P as $f).recip()),
+                }
+            }
+        }
+
+        impl<const P: u32> TryFrom<$f> for LpDistance<$f, ConstP<P>> {
+            type Error = NegativeDistanceError;
+
+            #[inline]
+            fn try_from(value: $f) -> Result<Self, Self::Error> {
+                if value >= 0.0 {
+                    Ok(Self::from_value(ConstP, value))
+                } else {
+                    Err(NegativeDistanceError)
+                }
+            }
+        }
+
+        impl<P: Exponent<$f>> From<LpDistance<$f, P>> for $f {
+            #[inline]
+            fn from(value: LpDistance<$f, P>) -> $f {
+                value.p.root(value.powered)
+            }
+        }
+
+        impl<P: Exponent<$f>> PartialOrd<$f> for LpDistance<$f, P> {
+            #[inline]
+            fn partial_cmp(&self, other: &$f) -> Option<Ordering> {
+                if *other >= 0.0 {
+                    self.powered.partial_cmp(&self.p.pow(*other))
+                } else {
+                    Some(Ordering::Greater)
+                }
+            }
+        }
+
+        impl<P: Exponent<$f>> PartialOrd<LpDistance<$f, P>> for $f {
+            #[inline]
+            fn partial_cmp(&self, other: &LpDistance<$f, P>) -> Option<Ordering> {
+                if *self >= 0.0 {
+                    other.p.pow(*self).partial_cmp(&other.powered)
+                } else {
+                    Some(Ordering::Less)
+                }
+            }
+        }
+
+        impl<P: Exponent<$f>> PartialEq<$f> for LpDistance<$f, P> {
+            #[inline]
+            fn eq(&self, other: &$f) -> bool {
+                self.partial_cmp(other) == Some(Ordering::Equal)
+            }
+        }
+
+        impl<P: Exponent<$f>> PartialEq<LpDistance<$f, P>> for $f {
+            #[inline]
+            fn eq(&self, other: &LpDistance<$f, P>) -> bool {
+                self.partial_cmp(other) == Some(Ordering::Equal)
+            }
+        }
+
+        impl<P: Exponent<$f>> Distance for LpDistance<$f, P> {
+            type Value = $f;
+        }
+    };
+}
+
+float_lp!(f32);
+float_lp!(f64);
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::kd::KdTree;
+    use crate::{ExactNeighbors, NearestNeighbors};
+
+    use rand::prelude::*;
+
+    use std::fmt::Debug;
+    use std::iter::FromIterator;
+
     #[test]
     fn test_lp_distance() {
         assert_eq!(l1_distance(&[0.0, 0.0], &[3.0, 4.0]), 7.0);
@@ -75,4 +533,95 @@ mod tests {
         assert!(lp_distance(3.0, &[0.0, 0.0], &[3.0, 4.0]) < 5.0);
         assert_eq!(linf_distance(&[0.0, 0.0], &[3.0, 4.0]), 4.0);
     }
+
+    #[test]
+    fn test_bounded_lp_distance() {
+        let distance = lp_distance(3.0, &[0.0, 0.0], &[3.0, 4.0]);
+        assert_eq!(bounded_lp_distance(3.0, &[0.0, 0.0], &[3.0, 4.0], 5.0), Some(distance));
+        assert_eq!(bounded_lp_distance(3.0, &[0.0, 0.0], &[3.0, 4.0], 4.0), None);
+        assert_eq!(bounded_lp_distance(3.0, &[0.0, 0.0], &[3.0, 4.0], -1.0), None);
+    }
+
+    #[test]
+    fn test_lp() {
+        let x = Lp::new(3.0, [0.0, 0.0]);
+        let y = Lp::new(3.0, [3.0, 4.0]);
+        let distance = x.distance(&y);
+        assert_eq!(distance.powered_value(), 91.0);
+        assert_eq!(distance.value(), lp_distance(3.0, &[0.0, 0.0], &[3.0, 4.0]));
+        assert_eq!(x.distance(&[3.0, 4.0]), distance);
+        assert_eq!([0.0, 0.0].distance(&y), distance);
+        assert!(4.0 < distance && distance < 5.0);
+        assert!(-1.0 < distance);
+
+        let bound = LpDistance::from_powered(3.0, 91.0);
+        assert_eq!(x.distance_bounded(&y, bound), Some(distance));
+        let bound = LpDistance::from_powered(3.0, 90.0);
+        assert_eq!(x.distance_bounded(&y, bound), None);
+
+        let x = Lp::new(ConstP::<2>, [0.0f32, 0.0]);
+        let y = Lp::new(ConstP::<2>, [3.0, 4.0]);
+        assert_eq!(x.distance(&y), 5.0);
+        assert_eq!(LpDistance::try_from(5.0f32).unwrap(), x.distance(&y));
+        assert!(LpDistance::<f32, ConstP<2>>::try_from(-5.0).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_lp_invalid() {
+        Lp::new(0.5, [0.0, 0.0]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Lp points have different exponents")]
+    fn test_lp_mixed() {
+        Lp::new(3.0, [0.0, 0.0]).distance(&Lp::new(1.5, [3.0, 4.0]));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Lp distances have different exponents")]
+    fn test_lp_distance_mixed() {
+        let _ = LpDistance::from_value(3.0, 1.0) < LpDistance::from_value(1.5, 2.0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_lp_serde() {
+        let x = Lp::new(3.0, [3.0, 4.0]);
+        let json = serde_json::to_string(&x).unwrap();
+        assert_eq!(serde_json::from_str::<Lp<[f64; 2], f64>>(&json).unwrap(), x);
+
+        let json = r#"{"p":0.5,"point":[3.0,4.0]}"#;
+        assert!(serde_json::from_str::<Lp<[f64; 2], f64>>(json).is_err());
+    }
+
+    fn test_kd_tree<P: Exponent<f64> + Debug + PartialEq>(p: P) {
+        let points: Vec<_> = (0..256)
+            .map(|_| Lp::new(p, [random::<f64>(), random(), random()]))
+            .collect();
+        let exhaustive = ExhaustiveSearch::from_iter(points.clone());
+        let kd = KdTree::from_iter(points);
+
+        for _ in 0..100 {
+            let target = Lp::new(p, [random(), random(), random()]);
+            let expected = exhaustive.k_nearest(&target, 3);
+            assert_eq!(kd.k_nearest(&target, 3), expected);
+
+            let radius = LpDistance::from_value(p, 0.2);
+            let expected = exhaustive.k_nearest_within(&target, 3, radius);
+            assert_eq!(kd.k_nearest_within(&target, 3, radius), expected);
+        }
+    }
+
+    #[test]
+    fn test_lp_kd_tree() {
+        fn exact<T: ExactNeighbors<K>, K: Proximity>(_: &T) {}
+        exact(&KdTree::<Lp<[f64; 3], f64>>::new());
+
+        test_kd_tree(1.5);
+        test_kd_tree(4.0);
+        test_kd_tree(ConstP::<3>);
+    }
 }
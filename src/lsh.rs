@@ -0,0 +1,607 @@
+//! [Locality-sensitive hashing](https://en.wikipedia.org/wiki/Locality-sensitive_hashing).
+
+use crate::coords::Coordinates;
+use crate::distance::Proximity;
+use crate::hamming::Hamming;
+use crate::util::{Ordered, Rng};
+use crate::{NearestNeighbors, Neighborhood};
+
+use num_traits::real::Real;
+use num_traits::{zero, PrimInt};
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::iter::{Extend, FromIterator};
+
+/// The default number of hash tables.
+const DEFAULT_TABLES: usize = 8;
+
+/// The default number of bits sampled for each table.
+const DEFAULT_BITS: usize = 8;
+
+/// The seed for the random hash function generator.
+const SEED: u64 = 0x4C53_484C_5348_4C53;
+
+/// One component of a locality-sensitive hash.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HashComponent {
+    /// The hash value.
+    pub value: i32,
+    /// The cost of probing `value - 1` instead, if that is a possible hash value.
+    pub down: Option<f64>,
+    /// The cost of probing `value + 1` instead, if that is a possible hash value.
+    pub up: Option<f64>,
+}
+
+impl HashComponent {
+    /// Create a hash component for a single bit.
+    ///
+    /// * `bit`: The value of the bit.
+    /// * `cost`: The cost of probing the flipped bit instead.
+    pub fn bit(bit: bool, cost: f64) -> Self {
+        if bit {
+            Self {
+                value: 1,
+                down: Some(cost),
+                up: None,
+            }
+        } else {
+            Self {
+                value: 0,
+                down: None,
+                up: Some(cost),
+            }
+        }
+    }
+}
+
+/// A [locality-sensitive hash] function.
+///
+/// Implementations hash points to a sequence of integer [components](HashComponent), such that
+/// nearby points are likely to have the same hash.  Each component also reports how close the
+/// point was to hashing to a neighboring value instead, which is used for multi-probe queries.
+///
+/// [locality-sensitive hash]: https://en.wikipedia.org/wiki/Locality-sensitive_hashing
+pub trait LshHash<T: ?Sized> {
+    /// Hash a point.
+    fn hash(&self, point: &T) -> Vec<HashComponent>;
+}
+
+/// The [bit sampling] hash family for [Hamming] space.
+///
+/// [bit sampling]: https://en.wikipedia.org/wiki/Locality-sensitive_hashing#Bit_sampling_for_Hamming_distance
+#[derive(Clone, Debug)]
+pub struct BitSampling {
+    /// The sampled bit positions.
+    bits: Vec<u32>,
+}
+
+impl BitSampling {
+    /// Create a random bit sampling hash.
+    ///
+    /// * `width`: The number of bits in the hashed integers.
+    /// * `k`: The number of bits to sample.
+    /// * `seed`: The random seed.
+    pub fn new(width: u32, k: usize, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let bits = (0..k)
+            .map(|_| rng.index(width as usize) as u32)
+            .collect();
+        Self { bits }
+    }
+
+    /// Hash an integer.
+    fn hash_int<T: PrimInt>(&self, x: T) -> Vec<HashComponent> {
+        self.bits
+            .iter()
+            .map(|&i| HashComponent::bit((x >> i as usize) & T::one() != T::zero(), 1.0))
+            .collect()
+    }
+}
+
+impl<T: PrimInt> LshHash<T> for BitSampling {
+    fn hash(&self, point: &T) -> Vec<HashComponent> {
+        self.hash_int(*point)
+    }
+}
+
+impl<T: PrimInt> LshHash<Hamming<T>> for BitSampling {
+    fn hash(&self, point: &Hamming<T>) -> Vec<HashComponent> {
+        self.hash_int(point.0)
+    }
+}
+
+/// Generate a random vector with normally distributed components.
+fn random_normal<T: Real>(rng: &mut Rng, dims: usize) -> Vec<T> {
+    (0..dims)
+        .map(|_| T::from(rng.next_normal()).unwrap())
+        .collect()
+}
+
+/// Compute the dot product between a vector and a point.
+fn dot<T: Coordinates>(vector: &[T::Value], point: &T) -> T::Value {
+    debug_assert!(point.dims() == vector.len());
+
+    let mut dot = zero();
+    for (i, &v) in vector.iter().enumerate() {
+        dot += v * point.coord(i);
+    }
+    dot
+}
+
+/// The [SimHash] family of random hyperplane hashes, for [angular distance].
+///
+/// [SimHash]: https://en.wikipedia.org/wiki/SimHash
+/// [angular distance]: crate::cos::Angular
+#[derive(Clone, Debug)]
+pub struct SimHash<T> {
+    /// The normal vectors of the random hyperplanes.
+    planes: Vec<Vec<T>>,
+}
+
+impl<T: Real> SimHash<T> {
+    /// Create a random SimHash.
+    ///
+    /// * `dims`: The number of dimensions of the hashed points.
+    /// * `k`: The number of random hyperplanes.
+    /// * `seed`: The random seed.
+    pub fn new(dims: usize, k: usize, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let planes = (0..k)
+            .map(|_| random_normal(&mut rng, dims))
+            .collect();
+        Self { planes }
+    }
+}
+
+impl<T, U> LshHash<U> for SimHash<T>
+where
+    T: Real,
+    U: Coordinates<Value = T>,
+{
+    fn hash(&self, point: &U) -> Vec<HashComponent> {
+        self.planes
+            .iter()
+            .map(|plane| {
+                let dot = dot(plane, point);
+                HashComponent::bit(dot >= zero(), dot.abs().to_f64().unwrap())
+            })
+            .collect()
+    }
+}
+
+/// The [p-stable] family of random projection hashes, for [Euclidean distance].
+///
+/// Each component is `$\lfloor (a \cdot x + b) / w \rfloor$`, where `$a$` is a random Gaussian
+/// vector, `$b$` is uniformly distributed in `$[0, w)$`, and `$w$` is the bucket width.
+///
+/// [p-stable]: https://en.wikipedia.org/wiki/Locality-sensitive_hashing#Stable_distributions
+/// [Euclidean distance]: crate::euclid::Euclidean
+#[derive(Clone, Debug)]
+pub struct PStable<T> {
+    /// The random projection vectors.
+    projections: Vec<Vec<T>>,
+    /// The random offsets.
+    offsets: Vec<T>,
+    /// The bucket width.
+    width: T,
+}
+
+impl<T: Real> PStable<T> {
+    /// Create a random p-stable hash.
+    ///
+    /// * `dims`: The number of dimensions of the hashed points.
+    /// * `k`: The number of random projections.
+    /// * `width`: The bucket width.  It should be around the expected nearest neighbor distance.
+    /// * `seed`: The random seed.
+    pub fn new(dims: usize, k: usize, width: T, seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let projections = (0..k)
+            .map(|_| random_normal(&mut rng, dims))
+            .collect();
+        let offsets = (0..k)
+            .map(|_| T::from(rng.next_f64()).unwrap() * width)
+            .collect();
+        Self {
+            projections,
+            offsets,
+            width,
+        }
+    }
+}
+
+impl<T, U> LshHash<U> for PStable<T>
+where
+    T: Real,
+    U: Coordinates<Value = T>,
+{
+    fn hash(&self, point: &U) -> Vec<HashComponent> {
+        self.projections
+            .iter()
+            .zip(&self.offsets)
+            .map(|(a, &b)| {
+                let x = (dot(a, point) + b) / self.width;
+                let floor = x.floor();
+                let frac = (x - floor).to_f64().unwrap();
+
+                // Far away projections saturate, and can't be perturbed past the ends of the range
+                let value = floor
+                    .to_f64()
+                    .unwrap()
+                    .max(i32::MIN.into())
+                    .min(i32::MAX.into()) as i32;
+                HashComponent {
+                    value,
+                    down: Some(frac).filter(|_| value > i32::MIN),
+                    up: Some(1.0 - frac).filter(|_| value < i32::MAX),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Generate the bucket keys to probe for a hash, in order of increasing cost.
+///
+/// The first key is always the unperturbed hash, followed by up to `probes` perturbed keys, as in
+/// [Multi-Probe LSH](https://www.cs.princeton.edu/cass/papers/mplsh_vldb07.pdf).
+fn probe_keys(components: &[HashComponent], probes: usize) -> Vec<Vec<i32>> {
+    let base: Vec<i32> = components.iter().map(|c| c.value).collect();
+    let mut keys = vec![base.clone()];
+
+    let mut perturbations = Vec::new();
+    for (i, c) in components.iter().enumerate() {
+        if let Some(cost) = c.down {
+            perturbations.push((cost, i, -1));
+        }
+        if let Some(cost) = c.up {
+            perturbations.push((cost, i, 1));
+        }
+    }
+    perturbations.sort_by_key(|&(cost, _, _)| Ordered::new(cost));
+
+    // A min-heap of perturbation sets, each a sorted list of indices into perturbations
+    let mut heap = BinaryHeap::new();
+    if let Some(&(cost, _, _)) = perturbations.first() {
+        heap.push(Reverse((Ordered::new(cost), vec![0])));
+    }
+
+    while keys.len() <= probes {
+        let (score, set) = match heap.pop() {
+            Some(Reverse((score, set))) => (score.into_inner(), set),
+            None => break,
+        };
+
+        let last = *set.last().unwrap();
+        let next = last + 1;
+        if next < perturbations.len() {
+            let next_cost = perturbations[next].0;
+
+            let mut shifted = set.clone();
+            *shifted.last_mut().unwrap() = next;
+            let shifted_score = score - perturbations[last].0 + next_cost;
+            heap.push(Reverse((Ordered::new(shifted_score), shifted)));
+
+            let mut expanded = set.clone();
+            expanded.push(next);
+            heap.push(Reverse((Ordered::new(score + next_cost), expanded)));
+        }
+
+        // Skip sets that perturb the same component twice
+        let mut key = base.clone();
+        let mut valid = true;
+        for &j in &set {
+            let (_, i, delta) = perturbations[j];
+            if key[i] != base[i] {
+                valid = false;
+                break;
+            }
+            key[i] += delta;
+        }
+        if valid {
+            keys.push(key);
+        }
+    }
+
+    keys
+}
+
+/// A single hash table in an LSH index.
+#[derive(Debug)]
+struct LshTable<H> {
+    /// The hash function for this table.
+    hash: H,
+    /// The indices of the items in each bucket.
+    buckets: HashMap<Vec<i32>, Vec<usize>>,
+}
+
+/// A [locality-sensitive hashing] index.
+///
+/// Items are stored in several hash tables, each with its own [`LshHash`] function.  Searches look
+/// up the target's bucket in each table, along with up to `probes` nearby buckets, and consider
+/// every item found.  More tables or more probes give more accurate results.
+///
+/// [locality-sensitive hashing]: https://en.wikipedia.org/wiki/Locality-sensitive_hashing
+#[derive(Debug)]
+pub struct Lsh<T, H> {
+    /// The indexed items.
+    items: Vec<T>,
+    /// The hash tables.
+    tables: Vec<LshTable<H>>,
+    /// The number of extra buckets to probe in each table.
+    probes: usize,
+}
+
+impl<T, H> Lsh<T, H> {
+    /// Create an empty index, with one hash table for each hash function.
+    pub fn new<I: IntoIterator<Item = H>>(hashes: I) -> Self {
+        let tables = hashes
+            .into_iter()
+            .map(|hash| LshTable {
+                hash,
+                buckets: HashMap::new(),
+            })
+            .collect();
+
+        Self {
+            items: Vec::new(),
+            tables,
+            probes: 0,
+        }
+    }
+
+    /// Get the number of hash tables.
+    pub fn tables(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Get the number of extra buckets probed in each table.
+    pub fn probes(&self) -> usize {
+        self.probes
+    }
+
+    /// Set the number of extra buckets probed in each table.
+    ///
+    /// Larger values give more accurate results, at the expense of slower searches.
+    pub fn set_probes(&mut self, probes: usize) {
+        self.probes = probes;
+    }
+
+    /// Get the size of this index.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if this index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<T: PrimInt> Lsh<Hamming<T>, BitSampling> {
+    /// Create an empty index using [bit sampling](BitSampling).
+    ///
+    /// * `tables`: The number of hash tables.
+    /// * `k`: The number of bits to sample for each table.
+    pub fn bit_sampling(tables: usize, k: usize) -> Self {
+        let width = T::zero().count_zeros();
+        let mut rng = Rng::new(SEED);
+        Self::new((0..tables).map(|_| BitSampling::new(width, k, rng.next_u64())))
+    }
+}
+
+impl<T: PrimInt> Default for Lsh<Hamming<T>, BitSampling> {
+    fn default() -> Self {
+        Self::bit_sampling(DEFAULT_TABLES, DEFAULT_BITS)
+    }
+}
+
+impl<T: PrimInt> FromIterator<Hamming<T>> for Lsh<Hamming<T>, BitSampling> {
+    fn from_iter<I: IntoIterator<Item = Hamming<T>>>(items: I) -> Self {
+        let mut index = Self::default();
+        index.extend(items);
+        index
+    }
+}
+
+impl<T> Lsh<T, SimHash<T::Value>>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    /// Create an empty index using [`SimHash`].
+    ///
+    /// * `dims`: The number of dimensions of the indexed points.
+    /// * `tables`: The number of hash tables.
+    /// * `k`: The number of random hyperplanes for each table.
+    pub fn simhash(dims: usize, tables: usize, k: usize) -> Self {
+        let mut rng = Rng::new(SEED);
+        Self::new((0..tables).map(|_| SimHash::new(dims, k, rng.next_u64())))
+    }
+}
+
+impl<T> Lsh<T, PStable<T::Value>>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    /// Create an empty index using [p-stable](PStable) hashes.
+    ///
+    /// * `dims`: The number of dimensions of the indexed points.
+    /// * `tables`: The number of hash tables.
+    /// * `k`: The number of random projections for each table.
+    /// * `width`: The bucket width.
+    pub fn p_stable(dims: usize, tables: usize, k: usize, width: T::Value) -> Self {
+        let mut rng = Rng::new(SEED);
+        Self::new((0..tables).map(|_| PStable::new(dims, k, width, rng.next_u64())))
+    }
+}
+
+impl<T, H: LshHash<T>> Lsh<T, H> {
+    /// Add a new item to the index.
+    pub fn push(&mut self, item: T) {
+        let index = self.items.len();
+
+        for table in &mut self.tables {
+            let key = table.hash.hash(&item).iter().map(|c| c.value).collect();
+            table.buckets.entry(key).or_insert_with(Vec::new).push(index);
+        }
+
+        self.items.push(item);
+    }
+}
+
+impl<T, H: LshHash<T>> Extend<T> for Lsh<T, H> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T, H> IntoIterator for Lsh<T, H> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<K, V, H> NearestNeighbors<K, V> for Lsh<V, H>
+where
+    K: Proximity<V>,
+    H: LshHash<K>,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        let target = neighborhood.target();
+
+        let mut candidates = Vec::new();
+        for table in &self.tables {
+            let components = table.hash.hash(target);
+            for key in probe_keys(&components, self.probes) {
+                if let Some(bucket) = table.buckets.get(&key) {
+                    candidates.extend_from_slice(bucket);
+                }
+            }
+        }
+
+        // The same item may be found in multiple tables
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        for i in candidates {
+            if neighborhood.is_exhausted() {
+                break;
+            }
+            neighborhood.consider_bounded(&self.items[i]);
+        }
+
+        neighborhood
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cos::Angular;
+    use crate::euclid::Euclidean;
+    use crate::exhaustive::ExhaustiveSearch;
+
+    use rand::prelude::*;
+
+    #[test]
+    fn test_probe_keys() {
+        let components = vec![
+            HashComponent { value: 0, down: Some(0.25), up: Some(0.75) },
+            HashComponent::bit(true, 0.5),
+        ];
+
+        assert_eq!(
+            probe_keys(&components, 8),
+            vec![
+                vec![0, 1],
+                vec![-1, 1],
+                vec![0, 0],
+                vec![-1, 0],
+                vec![1, 1],
+                vec![1, 0],
+            ],
+        );
+    }
+
+    #[test]
+    fn test_p_stable() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut random_point = || {
+            let mut coord = || rand::Rng::gen::<f64>(&mut rng);
+            Euclidean([coord(), coord(), coord()])
+        };
+        let points: Vec<_> = (0..256).map(|_| random_point()).collect();
+        let targets: Vec<_> = (0..100).map(|_| random_point()).collect();
+        let exhaustive = ExhaustiveSearch::from_iter(points.clone());
+
+        // The fraction of the true 5 nearest neighbors that are found
+        let recall = |probes| {
+            let mut index = Lsh::p_stable(3, 4, 2, 0.2);
+            index.set_probes(probes);
+            index.extend(points.iter().copied());
+
+            let mut found = 0;
+            for target in &targets {
+                let expected = exhaustive.k_nearest(target, 5);
+                let neighbors = index.k_nearest(target, 5);
+                found += neighbors.iter().filter(|n| expected.contains(n)).count();
+            }
+            found as f64 / (5 * targets.len()) as f64
+        };
+
+        let recalls: Vec<_> = [0, 2, 8, 32].iter().map(|&probes| recall(probes)).collect();
+        assert!(recalls.windows(2).all(|w| w[0] <= w[1]), "{:?}", recalls);
+        // Without probing, neighbors across bucket boundaries are often missed
+        assert!(recalls[0] < 0.9, "{:?}", recalls);
+        assert!(recalls[2] >= 0.9, "{:?}", recalls);
+    }
+
+    #[test]
+    fn test_p_stable_saturation() {
+        let hash = PStable::new(2, 4, 1e-3, 1);
+        for component in hash.hash(&[1e30, -1e30]) {
+            assert!(component.value == i32::MIN || component.value == i32::MAX);
+            assert!(component.down.is_none() || component.up.is_none());
+        }
+        assert_eq!(hash.hash(&[1e30, -1e30]), hash.hash(&[2e30, -2e30]));
+    }
+
+    #[test]
+    fn test_bit_sampling() {
+        let points: Vec<_> = (0..256).map(|_| Hamming(random::<u32>())).collect();
+        let index = Lsh::from_iter(points.clone());
+
+        for point in &points {
+            let nearest = index.nearest(point).expect("No nearest neighbor found");
+            assert_eq!(nearest.distance, 0);
+        }
+    }
+
+    #[test]
+    fn test_simhash() {
+        let points: Vec<_> = (0..256)
+            .map(|_| Angular([random::<f64>() - 0.5, random::<f64>() - 0.5, random()]))
+            .collect();
+
+        let mut index = Lsh::simhash(3, 4, 4);
+        index.set_probes(2);
+        index.extend(points.clone());
+
+        for point in &points {
+            let nearest = index.nearest(point).expect("No nearest neighbor found");
+            assert_eq!(nearest.item, point);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Parallel batch searches, powered by [rayon].
+//!
+//! This module is only available with the `rayon` feature.
+//!
+//! [rayon]: https://docs.rs/rayon
+
+use crate::distance::Proximity;
+use crate::{NearestNeighbors, Neighbor};
+
+use rayon::prelude::*;
+
+/// Parallel batch searches over a [`NearestNeighbors`] index.
+///
+/// Searches only need a shared reference to the index, so any index that is [`Sync`] can serve
+/// many queries at once.  This trait is implemented for all such indexes, and runs the queries on
+/// the current [rayon] thread pool.
+///
+///     use acap::euclid::Euclidean;
+///     use acap::par::ParNearestNeighbors;
+///     use acap::vp::FlatVpTree;
+///
+///     let tree = FlatVpTree::balanced(vec![
+///         Euclidean([3.0, 4.0]),
+///         Euclidean([5.0, 12.0]),
+///         Euclidean([8.0, 15.0]),
+///         Euclidean([7.0, 24.0]),
+///     ]);
+///
+///     let targets = [Euclidean([7.0, 7.0]), Euclidean([7.0, 20.0])];
+///     let nearest = tree.par_nearest(&targets);
+///     assert_eq!(nearest[0].unwrap().item, &Euclidean([3.0, 4.0]));
+///     assert_eq!(nearest[1].unwrap().item, &Euclidean([7.0, 24.0]));
+///
+/// [rayon]: https://docs.rs/rayon
+pub trait ParNearestNeighbors<K, V = K>: NearestNeighbors<K, V> + Sync
+where
+    K: Proximity<V> + Sync,
+    K::Distance: Send,
+    V: Sync,
+{
+    /// Returns the nearest neighbor to each of the `targets`.
+    ///
+    /// The results are in the same order as the targets.
+    fn par_nearest(&self, targets: &[K]) -> Vec<Option<Neighbor<&V, K::Distance>>> {
+        targets
+            .par_iter()
+            .map(|target| self.nearest(target))
+            .collect()
+    }
+
+    /// Returns the up to `k` nearest neighbors to each of the `targets`.
+    ///
+    /// The results are in the same order as the targets, and each one will be sorted from nearest
+    /// to farthest.
+    fn par_k_nearest(&self, targets: &[K], k: usize) -> Vec<Vec<Neighbor<&V, K::Distance>>> {
+        let mut neighbors = Vec::new();
+        neighbors.resize_with(targets.len(), || Vec::with_capacity(k));
+        self.par_merge_k_nearest(targets, k, &mut neighbors);
+        neighbors
+    }
+
+    /// Merges up to `k` nearest neighbors to each of the `targets` into existing sorted vectors.
+    ///
+    /// `neighbors[i]` receives the neighbors of `targets[i]`.  Reusing the same buffers across
+    /// batches (after clearing them) avoids allocating new result vectors for every query.
+    ///
+    /// # Panics
+    ///
+    /// If `targets` and `neighbors` have different lengths.
+    fn par_merge_k_nearest<'v>(
+        &'v self,
+        targets: &[K],
+        k: usize,
+        neighbors: &mut [Vec<Neighbor<&'v V, K::Distance>>],
+    ) {
+        assert_eq!(
+            targets.len(),
+            neighbors.len(),
+            "Mismatched targets and result buffers"
+        );
+
+        targets
+            .par_iter()
+            .zip(neighbors.par_iter_mut())
+            .for_each(|(target, neighbors)| self.merge_k_nearest(target, k, neighbors));
+    }
+}
+
+impl<K, V, T> ParNearestNeighbors<K, V> for T
+where
+    K: Proximity<V> + Sync,
+    K::Distance: Send,
+    V: Sync,
+    T: NearestNeighbors<K, V> + Sync,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::euclid::Euclidean;
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::kd::FlatKdTree;
+    use crate::vp::FlatVpTree;
+
+    use rand::prelude::*;
+
+    use std::iter::FromIterator;
+
+    type Point = Euclidean<[f32; 3]>;
+
+    fn test_par<T, F>(from_iter: F)
+    where
+        T: NearestNeighbors<Point> + Sync,
+        F: Fn(Vec<Point>) -> T,
+    {
+        let points: Vec<Point> = (0..255)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let index = from_iter(points);
+
+        let targets: Vec<Point> = (0..100)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+
+        let nearest = index.par_nearest(&targets);
+        let k_nearest = index.par_k_nearest(&targets, 3);
+        assert_eq!(nearest.len(), targets.len());
+        assert_eq!(k_nearest.len(), targets.len());
+        for (i, target) in targets.iter().enumerate() {
+            assert_eq!(nearest[i], index.nearest(target));
+            assert_eq!(k_nearest[i], index.k_nearest(target, 3));
+        }
+
+        let mut buffers = vec![Vec::new(); targets.len()];
+        index.par_merge_k_nearest(&targets, 3, &mut buffers);
+        assert_eq!(buffers, k_nearest);
+
+        for buffer in &mut buffers {
+            buffer.clear();
+        }
+        index.par_merge_k_nearest(&targets, 1, &mut buffers);
+        for (buffer, neighbors) in buffers.iter().zip(&k_nearest) {
+            assert_eq!(buffer[..], neighbors[..1]);
+        }
+    }
+
+    #[test]
+    fn test_par_exhaustive() {
+        test_par(ExhaustiveSearch::from_iter);
+    }
+
+    #[test]
+    fn test_par_flat_kd_tree() {
+        test_par(FlatKdTree::balanced);
+    }
+
+    #[test]
+    fn test_par_flat_vp_tree() {
+        test_par(FlatVpTree::balanced);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mismatched targets and result buffers")]
+    fn test_par_mismatched_buffers() {
+        let index = ExhaustiveSearch::from_iter(vec![Euclidean([0.0, 0.0, 0.0])]);
+        let targets = [Euclidean([1.0, 1.0, 1.0])];
+        index.par_merge_k_nearest(&targets, 1, &mut []);
+    }
+}
@@ -0,0 +1,490 @@
+//! [Product quantization](https://doi.org/10.1109/TPAMI.2010.57).
+
+use crate::coords::Coordinates;
+use crate::distance::{Distance, Proximity, Value};
+use crate::euclid::EuclideanDistance;
+use crate::ivf::kmeans;
+use crate::util::Ordered;
+use crate::{NearestNeighbors, Neighborhood};
+
+use num_traits::real::Real;
+use num_traits::zero;
+
+use std::collections::BinaryHeap;
+
+/// The number of centroids in each subspace codebook.
+const CODEBOOK_SIZE: usize = 256;
+
+/// The default number of candidates to re-rank.
+const DEFAULT_RERANK: usize = 64;
+
+/// A point encoded by a [`ProductQuantizer`], with one byte per subspace.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PqCode(pub Box<[u8]>);
+
+impl PqCode {
+    /// Get the bytes of this code.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Compute the squared Euclidean distance between a centroid and part of a point.
+fn squared_distance<P>(centroid: &[P::Value], point: &P, start: usize) -> P::Value
+where
+    P: ?Sized + Coordinates,
+{
+    let mut sum = zero();
+    for (i, &c) in centroid.iter().enumerate() {
+        let diff = c - point.coord(start + i);
+        sum += diff * diff;
+    }
+    sum
+}
+
+/// A [product quantizer].
+///
+/// Points are split into `m` subspaces of consecutive coordinates, and each subspace is quantized
+/// separately to one of 256 centroids, learned with k-means.  A quantized point is then stored in
+/// just `m` bytes.
+///
+/// [product quantizer]: https://doi.org/10.1109/TPAMI.2010.57
+#[derive(Clone, Debug)]
+pub struct ProductQuantizer<T> {
+    /// The first coordinate of each subspace, followed by the number of dimensions.
+    bounds: Vec<usize>,
+    /// The centroids of each subspace.
+    codebooks: Vec<Vec<Vec<T>>>,
+}
+
+impl<T: Real + Value> ProductQuantizer<T> {
+    /// Train a product quantizer.
+    ///
+    /// * `m`: The number of subspaces, and the number of bytes in each code.  Must be between 1
+    ///   and the number of dimensions.
+    /// * `points`: The training points.
+    pub fn train<P: Coordinates<Value = T>>(m: usize, points: &[P]) -> Self {
+        let dims = points.first().map_or(0, P::dims);
+        assert!(m >= 1, "m must be at least 1");
+        assert!(points.is_empty() || m <= dims, "m must be at most the number of dimensions");
+
+        let bounds: Vec<_> = (0..=m).map(|j| j * dims / m).collect();
+
+        let codebooks = bounds
+            .windows(2)
+            .map(|range| {
+                let subpoints: Vec<Vec<T>> = points
+                    .iter()
+                    .map(|p| (range[0]..range[1]).map(|i| p.coord(i)).collect())
+                    .collect();
+
+                if subpoints.is_empty() {
+                    Vec::new()
+                } else {
+                    kmeans(&subpoints, CODEBOOK_SIZE)
+                }
+            })
+            .collect();
+
+        Self { bounds, codebooks }
+    }
+
+    /// Get the number of subspaces.
+    pub fn subspaces(&self) -> usize {
+        self.codebooks.len()
+    }
+
+    /// Get the number of dimensions of the quantized points.
+    pub fn dims(&self) -> usize {
+        *self.bounds.last().unwrap()
+    }
+
+    /// Encode a point.
+    pub fn encode<P: ?Sized + Coordinates<Value = T>>(&self, point: &P) -> PqCode {
+        debug_assert!(point.dims() == self.dims());
+
+        self.codebooks
+            .iter()
+            .zip(&self.bounds)
+            .map(|(codebook, &start)| {
+                codebook
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, c)| Ordered::new(squared_distance(c, point, start)))
+                    .map(|(i, _)| i as u8)
+                    .expect("Cannot encode with an untrained quantizer")
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    /// Decode a point, returning the coordinates of its reconstruction.
+    pub fn decode(&self, code: &PqCode) -> Vec<T> {
+        self.codebooks
+            .iter()
+            .zip(code.as_bytes())
+            .flat_map(|(codebook, &c)| codebook[c as usize].iter().copied())
+            .collect()
+    }
+
+    /// Prepare a query for [asymmetric distance computation](PqQuery).
+    pub fn query<P: ?Sized + Coordinates<Value = T>>(&self, point: &P) -> PqQuery<T> {
+        debug_assert!(point.dims() == self.dims());
+
+        let tables = self
+            .codebooks
+            .iter()
+            .zip(&self.bounds)
+            .map(|(codebook, &start)| {
+                codebook
+                    .iter()
+                    .map(|c| squared_distance(c, point, start))
+                    .collect()
+            })
+            .collect();
+
+        PqQuery { tables }
+    }
+}
+
+impl From<Vec<u8>> for PqCode {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes.into_boxed_slice())
+    }
+}
+
+/// An unquantized query, for computing [asymmetric distances] to [PQ codes](PqCode).
+///
+/// A query holds a lookup table of the squared Euclidean distances from its subvectors to every
+/// centroid, so the distance to a code only takes one lookup per subspace.
+///
+/// [asymmetric distances]: https://doi.org/10.1109/TPAMI.2010.57
+#[derive(Clone, Debug)]
+pub struct PqQuery<T> {
+    /// The distance lookup table for each subspace.
+    tables: Vec<Vec<T>>,
+}
+
+/// The asymmetric distance approximates the Euclidean distance to the original point.
+impl<T> Proximity<PqCode> for PqQuery<T>
+where
+    T: Value,
+    EuclideanDistance<T>: Distance,
+{
+    type Distance = EuclideanDistance<T>;
+
+    fn distance(&self, code: &PqCode) -> Self::Distance {
+        let mut sum = zero();
+        for (table, &c) in self.tables.iter().zip(code.as_bytes()) {
+            sum += table[c as usize];
+        }
+        EuclideanDistance::from_squared(sum)
+    }
+}
+
+/// An index of [PQ codes](PqCode).
+///
+/// Searches compute the [asymmetric distance](PqQuery) from the target to every code.  Use a
+/// [`RerankedPqIndex`] to get exact distances to the original points.
+#[derive(Clone, Debug)]
+pub struct PqIndex<T> {
+    /// The quantizer that encodes the points.
+    quantizer: ProductQuantizer<T>,
+    /// The encoded points.
+    codes: Vec<PqCode>,
+}
+
+impl<T: Real + Value> PqIndex<T> {
+    /// Create an empty index.
+    pub fn new(quantizer: ProductQuantizer<T>) -> Self {
+        Self {
+            quantizer,
+            codes: Vec::new(),
+        }
+    }
+
+    /// Train a quantizer on a sequence of points, and encode them into a new index.
+    ///
+    /// * `m`: The number of subspaces, and the number of bytes in each code.
+    /// * `points`: The points to index.
+    pub fn with_params<P, I>(m: usize, points: I) -> Self
+    where
+        P: Coordinates<Value = T>,
+        I: IntoIterator<Item = P>,
+    {
+        let points: Vec<_> = points.into_iter().collect();
+        let mut index = Self::new(ProductQuantizer::train(m, &points));
+        for point in &points {
+            index.push(point);
+        }
+        index
+    }
+
+    /// Get the quantizer for this index.
+    pub fn quantizer(&self) -> &ProductQuantizer<T> {
+        &self.quantizer
+    }
+
+    /// Get the encoded points, in the order they were added.
+    pub fn codes(&self) -> &[PqCode] {
+        &self.codes
+    }
+
+    /// Prepare a query for searching this index.
+    pub fn query<P: ?Sized + Coordinates<Value = T>>(&self, point: &P) -> PqQuery<T> {
+        self.quantizer.query(point)
+    }
+
+    /// Encode a point and add it to the index.
+    pub fn push<P: ?Sized + Coordinates<Value = T>>(&mut self, point: &P) {
+        self.codes.push(self.quantizer.encode(point));
+    }
+
+    /// Get the size of this index.
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Check if this index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+}
+
+impl<T> NearestNeighbors<PqQuery<T>, PqCode> for PqIndex<T>
+where
+    T: Value,
+    EuclideanDistance<T>: Distance,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        PqQuery<T>: 'k,
+        PqCode: 'v,
+        N: Neighborhood<&'k PqQuery<T>, &'v PqCode>,
+    {
+        for code in &self.codes {
+            if neighborhood.is_exhausted() {
+                break;
+            }
+            neighborhood.consider_bounded(code);
+        }
+        neighborhood
+    }
+}
+
+/// A [`PqIndex`] that also keeps the original points, to re-rank its results exactly.
+///
+/// Searches find the `rerank` nearest codes by [asymmetric distance](PqQuery), then consider the
+/// corresponding original points.  The original points can be references to avoid copying them.
+#[derive(Clone, Debug)]
+pub struct RerankedPqIndex<T: Coordinates> {
+    /// The index of the encoded points.
+    index: PqIndex<T::Value>,
+    /// The original points.
+    items: Vec<T>,
+    /// The number of candidates to re-rank.
+    rerank: usize,
+}
+
+impl<T> RerankedPqIndex<T>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    /// Create an empty index.
+    pub fn new(quantizer: ProductQuantizer<T::Value>) -> Self {
+        Self {
+            index: PqIndex::new(quantizer),
+            items: Vec::new(),
+            rerank: DEFAULT_RERANK,
+        }
+    }
+
+    /// Train a quantizer on a sequence of points, and index them.
+    ///
+    /// * `m`: The number of subspaces, and the number of bytes in each code.
+    /// * `items`: The items to index.
+    pub fn with_params<I: IntoIterator<Item = T>>(m: usize, items: I) -> Self {
+        let items: Vec<_> = items.into_iter().collect();
+        let mut index = Self::new(ProductQuantizer::train(m, &items));
+        index.extend(items);
+        index
+    }
+
+    /// Add a new item to the index.
+    pub fn push(&mut self, item: T) {
+        self.index.push(&item);
+        self.items.push(item);
+    }
+}
+
+impl<T: Coordinates> RerankedPqIndex<T> {
+    /// Get the index of the encoded points.
+    pub fn codes(&self) -> &PqIndex<T::Value> {
+        &self.index
+    }
+
+    /// Get the number of candidates re-ranked during a search.
+    pub fn rerank(&self) -> usize {
+        self.rerank
+    }
+
+    /// Set the number of candidates re-ranked during a search.
+    ///
+    /// Larger values give more accurate results, at the expense of slower searches.
+    pub fn set_rerank(&mut self, rerank: usize) {
+        self.rerank = rerank.max(1);
+    }
+
+    /// Get the size of this index.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if this index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<T> Extend<T> for RerankedPqIndex<T>
+where
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Coordinates> IntoIterator for RerankedPqIndex<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for RerankedPqIndex<V>
+where
+    K: Proximity<V> + Coordinates<Value = V::Value>,
+    V: Coordinates,
+    V::Value: Real,
+    EuclideanDistance<V::Value>: Distance,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if self.is_empty() {
+            return neighborhood;
+        }
+
+        let query = self.index.query(neighborhood.target());
+
+        // A max-heap of the nearest codes found so far
+        let mut heap = BinaryHeap::with_capacity(self.rerank.min(self.len()) + 1);
+        for (i, code) in self.index.codes.iter().enumerate() {
+            let distance = Ordered::new(query.distance(code));
+            if heap.len() < self.rerank {
+                heap.push((distance, i));
+            } else if distance < heap.peek().unwrap().0 {
+                heap.pop();
+                heap.push((distance, i));
+            }
+        }
+
+        for (_, i) in heap.into_sorted_vec() {
+            if neighborhood.is_exhausted() {
+                break;
+            }
+            neighborhood.consider_bounded(&self.items[i]);
+        }
+
+        neighborhood
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::euclid::{euclidean_distance, Euclidean};
+    use crate::tests::test_nearest_neighbors;
+
+    use rand::prelude::*;
+
+    fn random_points(n: usize) -> Vec<Vec<f64>> {
+        (0..n)
+            .map(|_| (0..8).map(|_| random()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // With fewer points than centroids, every point is exactly representable
+        let points = random_points(200);
+        let quantizer = ProductQuantizer::train(4, &points);
+        assert_eq!(quantizer.subspaces(), 4);
+        assert_eq!(quantizer.dims(), 8);
+
+        for point in &points {
+            let code = quantizer.encode(point);
+            assert_eq!(code.as_bytes().len(), 4);
+            assert_eq!(&quantizer.decode(&code), point);
+        }
+    }
+
+    #[test]
+    fn test_asymmetric_distance() {
+        let points = random_points(1000);
+        let quantizer = ProductQuantizer::train(2, &points);
+
+        let target: Vec<f64> = (0..8).map(|_| random()).collect();
+        let query = quantizer.query(&target);
+
+        for point in &points {
+            let code = quantizer.encode(point);
+            let expected = euclidean_distance(&target, &quantizer.decode(&code));
+            let actual = query.distance(&code);
+            assert!((actual.value() - expected.value()).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_pq_index() {
+        let points = random_points(200);
+        let index = PqIndex::with_params(4, points.clone());
+
+        for (i, point) in points.iter().enumerate() {
+            let nearest = index.nearest(&index.query(point)).expect("No nearest neighbor found");
+            assert!(std::ptr::eq(nearest.item, &index.codes()[i]));
+            assert_eq!(nearest.distance, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_reranked_pq_index() {
+        test_nearest_neighbors(|points| {
+            let mut index = RerankedPqIndex::with_params(3, points);
+            index.set_rerank(usize::MAX);
+            index
+        });
+    }
+
+    #[test]
+    fn test_compressed_rerank() {
+        let points: Vec<_> = random_points(1000).into_iter().map(Euclidean).collect();
+        let index = RerankedPqIndex::with_params(2, points.iter());
+
+        for point in &points {
+            let nearest = index.nearest(&point).expect("No nearest neighbor found");
+            assert!(std::ptr::eq(*nearest.item, point));
+        }
+    }
+}
@@ -0,0 +1,563 @@
+//! Random projection forests, in the style of [Annoy](https://github.com/spotify/annoy).
+
+use crate::coords::Coordinates;
+use crate::cos::{Angular, Cosine, PrenormAngular, PrenormCosine};
+use crate::distance::{Proximity, Value};
+use crate::euclid::Euclidean;
+use crate::util::{Ordered, Rng};
+use crate::{NearestNeighbors, Neighborhood};
+
+use num_traits::real::Real;
+use num_traits::{one, zero};
+
+use std::collections::BinaryHeap;
+use std::iter::FromIterator;
+
+/// The default number of trees in a forest.
+const DEFAULT_TREES: usize = 8;
+
+/// The default maximum number of items in a leaf.
+const DEFAULT_LEAF_SIZE: usize = 16;
+
+/// The number of random splits to try before giving up.
+const SPLIT_ATTEMPTS: usize = 3;
+
+/// The seed for the random split generator.
+const SEED: u64 = 0x5250_464F_5245_5354;
+
+/// A hyperplane that splits a [coordinate space] in two.
+///
+/// [coordinate space]: Coordinates
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hyperplane<T> {
+    /// The unit normal vector.
+    normal: Vec<T>,
+    /// The offset from the origin along the normal.
+    offset: T,
+}
+
+impl<T: Value + Real> Hyperplane<T> {
+    /// Create the hyperplane `$\{x \mid n \cdot x = c\}$`.
+    ///
+    /// The normal vector `$n$` will be normalized to unit length, unless it is zero.
+    pub fn new(mut normal: Vec<T>, mut offset: T) -> Self {
+        let mut norm: T = zero();
+        for &n in &normal {
+            norm += n * n;
+        }
+        norm = norm.sqrt();
+
+        if norm > zero() {
+            for n in &mut normal {
+                *n /= norm;
+            }
+            offset /= norm;
+        }
+
+        Self { normal, offset }
+    }
+
+    /// Get the unit normal vector of this hyperplane.
+    pub fn normal(&self) -> &[T] {
+        &self.normal
+    }
+
+    /// Get the offset of this hyperplane from the origin.
+    pub fn offset(&self) -> T {
+        self.offset
+    }
+
+    /// Compute the signed distance `$n \cdot x - c$` between a point and this hyperplane.
+    ///
+    /// The result is positive on the side that the normal vector points towards.
+    pub fn margin<U: Coordinates<Value = T>>(&self, point: &U) -> T {
+        debug_assert!(point.dims() == self.normal.len());
+
+        let mut dot: T = zero();
+        for (i, &n) in self.normal.iter().enumerate() {
+            dot += n * point.coord(i);
+        }
+
+        dot - self.offset
+    }
+}
+
+/// Compute the hyperplane that bisects the segment between two points.
+fn euclidean_bisector<T, U>(x: T, y: U) -> Hyperplane<T::Value>
+where
+    T: Coordinates,
+    U: Coordinates<Value = T::Value>,
+    T::Value: Real,
+{
+    debug_assert!(x.dims() == y.dims());
+
+    let mut normal = Vec::with_capacity(x.dims());
+    let mut offset: T::Value = zero();
+    for i in 0..x.dims() {
+        let xi = x.coord(i);
+        let yi = y.coord(i);
+        normal.push(xi - yi);
+        offset += xi * xi - yi * yi;
+    }
+
+    let two: T::Value = one::<T::Value>() + one();
+    Hyperplane::new(normal, offset / two)
+}
+
+/// Compute the hyperplane through the origin that bisects the angle between two points.
+fn angular_bisector<T, U>(x: T, y: U, normalize: bool) -> Hyperplane<T::Value>
+where
+    T: Coordinates,
+    U: Coordinates<Value = T::Value>,
+    T::Value: Real,
+{
+    debug_assert!(x.dims() == y.dims());
+
+    let mut xx: T::Value = one();
+    let mut yy: T::Value = one();
+    if normalize {
+        xx = zero();
+        yy = zero();
+        for i in 0..x.dims() {
+            let xi = x.coord(i);
+            let yi = y.coord(i);
+            xx += xi * xi;
+            yy += yi * yi;
+        }
+        xx = xx.sqrt();
+        yy = yy.sqrt();
+    }
+
+    let normal = (0..x.dims())
+        .map(|i| x.coord(i) / xx - y.coord(i) / yy)
+        .collect();
+    Hyperplane::new(normal, zero())
+}
+
+/// A [`Proximity`] over a [coordinate space] that can be split by [`Hyperplane`]s.
+///
+/// [coordinate space]: Coordinates
+pub trait RpProximity: Coordinates + Proximity
+where
+    Self::Value: Real,
+{
+    /// Compute a hyperplane that separates the points nearer to `self` from those nearer to `other`.
+    ///
+    /// Points nearer to `self` should have positive [margins](Hyperplane::margin).
+    fn bisector(&self, other: &Self) -> Hyperplane<Self::Value>;
+}
+
+/// Euclidean space is split by perpendicular bisectors.
+impl<T> RpProximity for Euclidean<T>
+where
+    Self: Proximity,
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn bisector(&self, other: &Self) -> Hyperplane<Self::Value> {
+        euclidean_bisector(self, other)
+    }
+}
+
+/// Cosine distance is split by angle bisectors.
+impl<T> RpProximity for Cosine<T>
+where
+    Self: Proximity,
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn bisector(&self, other: &Self) -> Hyperplane<Self::Value> {
+        angular_bisector(self, other, true)
+    }
+}
+
+/// Cosine distance is split by angle bisectors.
+impl<T> RpProximity for PrenormCosine<T>
+where
+    Self: Proximity,
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn bisector(&self, other: &Self) -> Hyperplane<Self::Value> {
+        angular_bisector(self, other, false)
+    }
+}
+
+/// Angular distance is split by angle bisectors.
+impl<T> RpProximity for Angular<T>
+where
+    Self: Proximity,
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn bisector(&self, other: &Self) -> Hyperplane<Self::Value> {
+        angular_bisector(self, other, true)
+    }
+}
+
+/// Angular distance is split by angle bisectors.
+impl<T> RpProximity for PrenormAngular<T>
+where
+    Self: Proximity,
+    T: Coordinates,
+    T::Value: Real,
+{
+    fn bisector(&self, other: &Self) -> Hyperplane<Self::Value> {
+        angular_bisector(self, other, false)
+    }
+}
+
+/// A node in a random projection tree.
+#[derive(Debug)]
+enum RpNode<T> {
+    /// An internal node, split by a hyperplane.
+    Split {
+        /// The splitting hyperplane.
+        plane: Hyperplane<T>,
+        /// The subtree on the positive side of the hyperplane.
+        left: usize,
+        /// The subtree on the negative side of the hyperplane.
+        right: usize,
+    },
+    /// A leaf node, holding a range of item indices.
+    Leaf {
+        /// The start of the index range.
+        start: usize,
+        /// The end of the index range.
+        end: usize,
+    },
+}
+
+/// Builds random projection trees.
+struct RpBuilder<'a, T: Coordinates> {
+    /// The items being indexed.
+    items: &'a [T],
+    /// The nodes built so far.
+    nodes: Vec<RpNode<T::Value>>,
+    /// The maximum number of items per leaf.
+    leaf_size: usize,
+    /// The random split generator.
+    rng: Rng,
+}
+
+impl<'a, T> RpBuilder<'a, T>
+where
+    T: RpProximity,
+    T::Value: Real,
+{
+    /// Add a node, returning its index.
+    fn push(&mut self, node: RpNode<T::Value>) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Recursively build a tree over a range of item indices.
+    fn build(&mut self, indices: &mut [usize], start: usize) -> usize {
+        let len = indices.len();
+        if len <= self.leaf_size {
+            return self.push(RpNode::Leaf {
+                start,
+                end: start + len,
+            });
+        }
+
+        let mut split = None;
+        for _ in 0..SPLIT_ATTEMPTS {
+            let i = self.rng.index(len);
+            let mut j = self.rng.index(len - 1);
+            if j >= i {
+                j += 1;
+            }
+
+            let plane = self.items[indices[i]].bisector(&self.items[indices[j]]);
+            let mid = partition(indices, |k| plane.margin(&self.items[k]) > zero());
+            if mid > 0 && mid < len {
+                split = Some((plane, mid));
+                break;
+            }
+        }
+
+        // If no split was found (e.g. due to duplicates), split arbitrarily
+        let (plane, mid) = split.unwrap_or_else(|| {
+            let dims = self.items[indices[0]].dims();
+            (Hyperplane::new(vec![zero(); dims], zero()), len / 2)
+        });
+
+        let (left, right) = indices.split_at_mut(mid);
+        let left = self.build(left, start);
+        let right = self.build(right, start + mid);
+        self.push(RpNode::Split { plane, left, right })
+    }
+}
+
+/// Partition a slice in place, returning the number of elements that satisfy the predicate.
+fn partition<F: FnMut(usize) -> bool>(indices: &mut [usize], mut pred: F) -> usize {
+    let mut mid = 0;
+    for i in 0..indices.len() {
+        if pred(indices[i]) {
+            indices.swap(mid, i);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// A forest of [random projection trees].
+///
+/// Each tree recursively splits the items by the [`Hyperplane`] between two randomly sampled
+/// items, until at most `leaf_size` items remain.  Searches explore all the trees at once, using a
+/// shared priority queue ordered by the distance to the splitting hyperplanes, until `search_k`
+/// candidates have been found.  Like [Annoy], `search_k` defaults to the number of trees times the
+/// number of neighbors requested.  More trees or a larger `search_k` give more accurate results.
+///
+/// [random projection trees]: https://en.wikipedia.org/wiki/Random_projection
+/// [Annoy]: https://github.com/spotify/annoy
+#[derive(Debug)]
+pub struct RpForest<T: Coordinates> {
+    /// The indexed items.
+    items: Vec<T>,
+    /// The nodes of every tree.
+    nodes: Vec<RpNode<T::Value>>,
+    /// The root node of each tree.
+    roots: Vec<usize>,
+    /// The item indices, permuted so each leaf refers to a contiguous range.
+    indices: Vec<usize>,
+    /// The maximum number of items in each leaf.
+    leaf_size: usize,
+    /// The maximum number of candidates to collect during a search, if not the default.
+    search_k: Option<usize>,
+}
+
+impl<T> RpForest<T>
+where
+    T: RpProximity,
+    T::Value: Real,
+{
+    /// Create a forest out of a sequence of items, with the default parameters.
+    pub fn balanced<I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::with_params(DEFAULT_TREES, DEFAULT_LEAF_SIZE, items)
+    }
+
+    /// Create a forest out of a sequence of items.
+    ///
+    /// * `trees`: The number of trees to build.
+    /// * `leaf_size`: The maximum number of items in each leaf.
+    /// * `items`: The items to index.
+    pub fn with_params<I>(trees: usize, leaf_size: usize, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let items: Vec<T> = items.into_iter().collect();
+        let leaf_size = leaf_size.max(1);
+
+        let mut builder = RpBuilder {
+            items: &items,
+            nodes: Vec::new(),
+            leaf_size,
+            rng: Rng::new(SEED),
+        };
+
+        let len = items.len();
+        let mut roots = Vec::with_capacity(trees);
+        let mut indices = Vec::with_capacity(trees * len);
+        for _ in 0..trees {
+            let start = indices.len();
+            indices.extend(0..len);
+            roots.push(builder.build(&mut indices[start..], start));
+        }
+
+        let nodes = builder.nodes;
+
+        Self {
+            items,
+            nodes,
+            roots,
+            indices,
+            leaf_size,
+            search_k: None,
+        }
+    }
+}
+
+impl<T: Coordinates> RpForest<T> {
+    /// Get the number of trees in this forest.
+    pub fn trees(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Get the maximum number of candidates collected during a search, if it has been set.
+    pub fn search_k(&self) -> Option<usize> {
+        self.search_k
+    }
+
+    /// Set the maximum number of candidates collected during a search.
+    ///
+    /// Larger values give more accurate results, at the expense of slower searches.  `None` means
+    /// [`trees()`](Self::trees) times the [capacity](Neighborhood::capacity) of the neighborhood
+    /// being searched, or times the leaf size if its capacity is unlimited.  Either way, searches
+    /// keep going until they have found enough distinct candidates to fill the neighborhood, if the
+    /// forest has that many items.
+    pub fn set_search_k(&mut self, search_k: Option<usize>) {
+        self.search_k = search_k;
+    }
+
+    /// Get the size of this forest.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if this forest is empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<T> FromIterator<T> for RpForest<T>
+where
+    T: RpProximity,
+    T::Value: Real,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::balanced(items)
+    }
+}
+
+impl<T: Coordinates> IntoIterator for RpForest<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<K, V> NearestNeighbors<K, V> for RpForest<V>
+where
+    K: Proximity<V> + Coordinates<Value = V::Value>,
+    V: Coordinates,
+    V::Value: Real,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        let target = neighborhood.target();
+
+        let max = V::Value::max_value();
+        let mut queue: BinaryHeap<_> = self
+            .roots
+            .iter()
+            .map(|&root| (Ordered::new(max), root))
+            .collect();
+
+        let capacity = neighborhood.capacity();
+        let per_tree = capacity.unwrap_or(self.leaf_size);
+        let search_k = self
+            .search_k
+            .unwrap_or_else(|| self.trees().saturating_mul(per_tree));
+        let min_unique = capacity.unwrap_or(0);
+        let limit = search_k.max(min_unique);
+
+        let mut candidates = Vec::new();
+        loop {
+            while candidates.len() < limit {
+                let (priority, node) = match queue.pop() {
+                    Some((priority, node)) => (priority.into_inner(), node),
+                    None => break,
+                };
+
+                match &self.nodes[node] {
+                    RpNode::Split { plane, left, right } => {
+                        let margin = plane.margin(target);
+                        queue.push((Ordered::new(priority.min(margin)), *left));
+                        queue.push((Ordered::new(priority.min(-margin)), *right));
+                    }
+                    RpNode::Leaf { start, end } => {
+                        candidates.extend_from_slice(&self.indices[*start..*end]);
+                    }
+                }
+            }
+
+            // The same item may be found in multiple trees
+            candidates.sort_unstable();
+            candidates.dedup();
+
+            if candidates.len() >= min_unique || queue.is_empty() {
+                break;
+            }
+        }
+
+        for i in candidates {
+            if neighborhood.is_exhausted() {
+                break;
+            }
+            neighborhood.consider_bounded(&self.items[i]);
+        }
+
+        neighborhood
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::tests::test_nearest_neighbors;
+
+    use rand::prelude::*;
+
+    #[test]
+    fn test_bisector() {
+        let plane = Euclidean([1.0, 1.0]).bisector(&Euclidean([3.0, 1.0]));
+        assert_eq!(plane.normal(), &[-1.0, 0.0]);
+        assert_eq!(plane.offset(), -2.0);
+        assert_eq!(plane.margin(&[0.0, 5.0]), 2.0);
+
+        let plane = Angular([2.0, 0.0]).bisector(&Angular([0.0, 3.0]));
+        assert_eq!(plane.offset(), 0.0);
+        assert!(plane.margin(&[1.0, 0.5]) > 0.0);
+        assert!(plane.margin(&[0.5, 1.0]) < 0.0);
+    }
+
+    #[test]
+    fn test_rp_forest() {
+        test_nearest_neighbors(|points| {
+            let mut forest = RpForest::with_params(4, 8, points);
+            forest.set_search_k(Some(usize::MAX));
+            forest
+        });
+    }
+
+    #[test]
+    fn test_angular_rp_forest() {
+        let mut points = Vec::new();
+        for _ in 0..256 {
+            points.push(Angular([random::<f64>() - 0.5, random::<f64>() - 0.5, random()]));
+        }
+
+        let mut forest = RpForest::with_params(4, 8, points.clone());
+        forest.set_search_k(Some(usize::MAX));
+        let eindex = ExhaustiveSearch::from_iter(points);
+
+        let target = Angular([random(), random(), random()]);
+        assert_eq!(forest.k_nearest(&target, 3), eindex.k_nearest(&target, 3));
+    }
+
+    #[test]
+    fn test_rp_forest_default_search_k() {
+        let points: Vec<_> = (0..2000)
+            .map(|_| Euclidean([random::<f64>(), random(), random()]))
+            .collect();
+        let forest = RpForest::from_iter(points);
+        assert_eq!(forest.search_k(), None);
+
+        let k = DEFAULT_TREES * DEFAULT_LEAF_SIZE + 72;
+        let target = Euclidean([random(), random(), random()]);
+        assert_eq!(forest.k_nearest(&target, k).len(), k);
+        assert_eq!(forest.k_nearest(&target, 2000).len(), 2000);
+        assert!(forest.nearest(&target).is_some());
+    }
+}
@@ -6,6 +6,9 @@ use crate::lp::Minkowski;
 
 use num_traits::{zero, Signed};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// A point in taxicab space.
 ///
 /// This wrapper equips any [coordinate space] with the [taxicab distance] metric.
@@ -13,6 +16,8 @@ use num_traits::{zero, Signed};
 /// [coordinate space]: Coordinates
 /// [taxicab distance]: taxicab_distance
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[repr(transparent)]
 pub struct Taxicab<T>(pub T);
 
 impl<T> Taxicab<T> {
@@ -69,6 +74,30 @@ where
     sum
 }
 
+/// Compute the [taxicab distance] between two points, if it is at most `bound`.
+///
+/// The sum is checked against the bound as it goes, so points that are far apart can be rejected
+/// without looking at all of their coordinates.
+///
+/// [taxicab distance]: https://en.wikipedia.org/wiki/Taxicab_geometry
+pub fn bounded_taxicab_distance<T, U>(x: T, y: U, bound: T::Value) -> Option<T::Value>
+where
+    T: Coordinates,
+    U: Coordinates<Value = T::Value>,
+{
+    debug_assert!(x.dims() == y.dims());
+
+    let mut sum = zero();
+    for i in 0..x.dims() {
+        sum += (x.coord(i) - y.coord(i)).abs();
+        if sum > bound {
+            return None;
+        }
+    }
+
+    Some(sum).filter(|&sum| sum <= bound)
+}
+
 /// The taxicab distance function.
 impl<T: Coordinates> Proximity for Taxicab<T> {
     type Distance = T::Value;
@@ -76,6 +105,10 @@ impl<T: Coordinates> Proximity for Taxicab<T> {
     fn distance(&self, other: &Self) -> Self::Distance {
         taxicab_distance(self, other)
     }
+
+    fn distance_bounded(&self, other: &Self, bound: Self::Distance) -> Option<Self::Distance> {
+        bounded_taxicab_distance(self, other, bound)
+    }
 }
 
 impl<T: Coordinates> Proximity<T> for Taxicab<T> {
@@ -84,6 +117,10 @@ impl<T: Coordinates> Proximity<T> for Taxicab<T> {
     fn distance(&self, other: &T) -> Self::Distance {
         taxicab_distance(self, other)
     }
+
+    fn distance_bounded(&self, other: &T, bound: Self::Distance) -> Option<Self::Distance> {
+        bounded_taxicab_distance(self, other, bound)
+    }
 }
 
 impl<T: Coordinates> Proximity<Taxicab<T>> for T {
@@ -92,6 +129,14 @@ impl<T: Coordinates> Proximity<Taxicab<T>> for T {
     fn distance(&self, other: &Taxicab<T>) -> Self::Distance {
         taxicab_distance(self, other)
     }
+
+    fn distance_bounded(
+        &self,
+        other: &Taxicab<T>,
+        bound: Self::Distance,
+    ) -> Option<Self::Distance> {
+        bounded_taxicab_distance(self, other, bound)
+    }
 }
 
 /// Taxicab distance is a metric.
@@ -120,4 +165,15 @@ mod tests {
         assert_eq!(Taxicab([-3, 4]).distance(&[4, -3]), 14);
         assert_eq!([-3, 4].distance(&Taxicab([4, -3])), 14);
     }
+
+    #[test]
+    fn test_bounded() {
+        assert_eq!(bounded_taxicab_distance([-3, 4], [4, -3], 14), Some(14));
+        assert_eq!(bounded_taxicab_distance([-3, 4], [4, -3], 13), None);
+        assert_eq!(bounded_taxicab_distance([-3, 4], [4, -3], -1), None);
+
+        assert_eq!(Taxicab([-3, 4]).distance_bounded(&Taxicab([4, -3]), 20), Some(14));
+        assert_eq!(Taxicab([-3, 4]).distance_bounded(&[4, -3], 7), None);
+        assert_eq!([-3, 4].distance_bounded(&Taxicab([4, -3]), 7), None);
+    }
 }
@@ -2,6 +2,10 @@
 
 use std::cmp::Ordering;
 
+/// The smallest subtree that is worth building in parallel.
+#[cfg(feature = "rayon")]
+pub const PAR_MIN_LEN: usize = 1 << 12;
+
 /// A wrapper that converts a partial ordering into a total one by panicking.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub struct Ordered<T>(T);
@@ -11,6 +15,11 @@ impl<T> Ordered<T> {
     pub fn new(item: T) -> Self {
         Self(item)
     }
+
+    /// Unwrap a value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
 }
 
 impl<T> From<T> for Ordered<T> {
@@ -27,6 +36,116 @@ impl<T: PartialOrd> Ord for Ordered<T> {
 
 impl<T: PartialEq> Eq for Ordered<T> {}
 
+/// An entry in the priority queue of a best-first search.
+///
+/// Entries are ordered so that a [`BinaryHeap`] pops the nearest one first, with items breaking
+/// ties before subtrees.
+///
+/// [`BinaryHeap`]: std::collections::BinaryHeap
+#[derive(Debug)]
+pub enum BestFirst<N, V, B, D> {
+    /// A subtree, with a lower bound on the distance to anything inside it.
+    Node(N, B),
+    /// An item, with its exact distance.
+    Item(V, D),
+}
+
+impl<N, V, B, D> BestFirst<N, V, B, D>
+where
+    B: PartialOrd + PartialOrd<D>,
+    D: PartialOrd,
+{
+    /// Compare the distances of two entries, treating items as nearer in case of ties.
+    fn cmp_distance(&self, other: &Self) -> Ordering {
+        use BestFirst::*;
+
+        let ordering = match (self, other) {
+            (Node(_, a), Node(_, b)) => a.partial_cmp(b),
+            (Item(_, a), Item(_, b)) => a.partial_cmp(b),
+            (Node(_, a), Item(_, b)) => a.partial_cmp(b).map(|o| o.then(Ordering::Greater)),
+            (Item(_, a), Node(_, b)) => b.partial_cmp(a).map(|o| o.reverse().then(Ordering::Less)),
+        };
+        ordering.expect("Comparison between unordered items")
+    }
+}
+
+impl<N, V, B, D> PartialEq for BestFirst<N, V, B, D>
+where
+    B: PartialOrd + PartialOrd<D>,
+    D: PartialOrd,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N, V, B, D> Eq for BestFirst<N, V, B, D>
+where
+    B: PartialOrd + PartialOrd<D>,
+    D: PartialOrd,
+{}
+
+impl<N, V, B, D> PartialOrd for BestFirst<N, V, B, D>
+where
+    B: PartialOrd + PartialOrd<D>,
+    D: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N, V, B, D> Ord for BestFirst<N, V, B, D>
+where
+    B: PartialOrd + PartialOrd<D>,
+    D: PartialOrd,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, since BinaryHeap is a max-heap
+        self.cmp_distance(other).reverse()
+    }
+}
+
+/// A small, fast, deterministic pseudo-random number generator ([SplitMix64]).
+///
+/// [SplitMix64]: https://prng.di.unimi.it/splitmix64.c
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Create a new generator from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Generate a random 64-bit integer.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Generate a random index uniformly distributed in `$[0, n)$`.
+    pub fn index(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    /// Generate a random float uniformly distributed in `$[0, 1)$`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Generate a random float from the standard normal distribution.
+    pub fn next_normal(&mut self) -> f64 {
+        // Box-Muller transform, with 1.0 - u in (0, 1] to avoid ln(0)
+        let u = 1.0 - self.next_f64();
+        let v = self.next_f64();
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -41,6 +160,44 @@ mod tests {
         assert_eq!(two.cmp(&one), Ordering::Greater);
     }
 
+    #[test]
+    fn test_best_first() {
+        use std::collections::BinaryHeap;
+
+        let mut heap = BinaryHeap::new();
+        heap.push(BestFirst::Node("b", 1.0));
+        heap.push(BestFirst::Item("c", 2.0));
+        heap.push(BestFirst::Item("a", 1.0));
+        heap.push(BestFirst::Node("d", 3.0));
+
+        let order: Vec<_> = std::iter::from_fn(|| heap.pop())
+            .map(|e| match e {
+                BestFirst::Node(n, _) => n,
+                BestFirst::Item(v, _) => v,
+            })
+            .collect();
+        assert_eq!(order, vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_rng() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+
+            let x = a.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            b.next_f64();
+
+            assert!(a.index(10) < 10);
+            b.index(10);
+
+            assert!(a.next_normal().is_finite());
+            b.next_normal();
+        }
+    }
+
     #[test]
     #[should_panic(expected = "Comparison between unordered items")]
     fn test_unordered() {
@@ -1,12 +1,25 @@
 //! [Vantage-point trees](https://en.wikipedia.org/wiki/Vantage-point_tree).
 
+use crate::approx::Approximate;
+use crate::coords::Coordinates;
 use crate::distance::{Distance, DistanceValue, Metric, Proximity};
-use crate::util::Ordered;
-use crate::{ExactNeighbors, NearestNeighbors, Neighborhood};
+use crate::flat::{FormatError, Kind, Pod, Reader, Scalar, Writer};
+use crate::util::{BestFirst, Ordered};
+#[cfg(feature = "rayon")]
+use crate::util::PAR_MIN_LEN;
+use crate::{ExactNeighbors, NearestNeighbors, Neighbor, Neighborhood};
 
-use num_traits::zero;
+use num_traits::{one, zero};
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+#[cfg(feature = "serde")]
+use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+use std::collections::BinaryHeap;
 use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Write};
 use std::iter::{Extend, FromIterator};
 use std::ops::Deref;
 
@@ -34,87 +47,170 @@ impl<T: Proximity> VpNode<T> {
         }
     }
 
-    /// Create a balanced tree.
-    fn balanced<I: IntoIterator<Item = T>>(items: I) -> Option<Self> {
-        let mut nodes: Vec<_> = items
+    /// Create some unlinked nodes out of a sequence of items.
+    fn nodes<I: IntoIterator<Item = T>>(items: I) -> Vec<Option<Box<Self>>> {
+        items
             .into_iter()
             .map(Self::new)
             .map(Box::new)
             .map(Some)
-            .collect();
+            .collect()
+    }
+
+    /// Unlink every node in a list from its descendants, appending them to the list.
+    fn unlink(nodes: &mut Vec<Option<Box<Self>>>) {
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = nodes[i].as_mut().unwrap();
+            let inside = node.inside.take();
+            let outside = node.outside.take();
+            if inside.is_some() {
+                nodes.push(inside);
+            }
+            if outside.is_some() {
+                nodes.push(outside);
+            }
 
-        Self::balanced_recursive(&mut nodes)
-            .map(|node| *node)
+            i += 1;
+        }
     }
 
     /// Create a balanced subtree.
     fn balanced_recursive(nodes: &mut [Option<Box<Self>>]) -> Option<Box<Self>> {
         if let Some((node, children)) = nodes.split_first_mut() {
             let mut node = node.take().unwrap();
-            children.sort_by_cached_key(|x| Ordered::new(node.distance_to_box(x)));
-
-            let (inside, outside) = children.split_at_mut(children.len() / 2);
-            if let Some(last) = inside.last() {
-                node.radius = node.distance_to_box(last).value();
-            }
-
-            node.inside = Self::balanced_recursive(inside);
-            node.outside = Self::balanced_recursive(outside);
-
+            node.balance_children(children);
             Some(node)
         } else {
             None
         }
     }
 
-    /// Get the distance between to boxed nodes.
-    fn distance_to_box(&self, child: &Option<Box<Self>>) -> T::Distance {
-        self.item.distance(&child.as_ref().unwrap().item)
+    /// Build balanced subtrees below this node, out of some unlinked nodes.
+    fn balance_children(&mut self, children: &mut [Option<Box<Self>>]) {
+        // Stash the distances in the children's radii, to compute them only once
+        for child in children.iter_mut() {
+            let child = child.as_mut().unwrap();
+            child.radius = self.item.distance(&child.item).value();
+        }
+
+        // Select the median distance, rather than sorting
+        let mid = children.len() / 2;
+        self.radius = if mid > 0 {
+            children.select_nth_unstable_by_key(mid - 1, |x| Ordered::new(Self::radius_of(x)));
+            Self::radius_of(&children[mid - 1])
+        } else {
+            zero()
+        };
+
+        let (inside, outside) = children.split_at_mut(mid);
+
+        self.inside = Self::balanced_recursive(inside);
+        self.outside = Self::balanced_recursive(outside);
+    }
+
+    /// Get the radius of a boxed node.
+    fn radius_of(node: &Option<Box<Self>>) -> DistanceValue<T> {
+        node.as_ref().unwrap().radius
     }
 
     /// Push a new item into this subtree.
-    fn push(&mut self, item: T)
-    where
-        // https://github.com/rust-lang/rust/issues/72582
-        T::Distance: PartialOrd + PartialOrd<DistanceValue<T>>,
-    {
+    fn push(&mut self, item: T) {
         match (&mut self.inside, &mut self.outside) {
             (None, None) => {
+                // The old radius may be left over from children that were removed
+                self.radius = zero();
                 self.outside = Some(Box::new(Self::new(item)));
             }
             (Some(inside), Some(outside)) => {
-                if self.item.distance(&item) <= self.radius {
+                // Compare the distance values, so remove() can find this item the same way
+                let distance: DistanceValue<T> = self.item.distance(&item).into();
+                if distance <= self.radius {
                     inside.push(item);
                 } else {
                     outside.push(item);
                 }
             }
             _ => {
-                let node = Box::new(Self::new(item));
-                let other = self.inside.take().xor(self.outside.take()).unwrap();
+                // After a remove(), the remaining child may have its own descendants, so rebuild
+                // both sides around a new radius
+                let other = self.inside.take().xor(self.outside.take());
+                let mut nodes = vec![other, Some(Box::new(Self::new(item)))];
+                Self::unlink(&mut nodes);
+                self.balance_children(&mut nodes);
+            }
+        }
+    }
 
-                let r1 = self.item.distance(&node.item);
-                let r2 = self.item.distance(&other.item);
+    /// Remove an item from the subtree in a slot, if it's present.
+    fn remove(slot: &mut Option<Box<Self>>, item: &T) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let node = slot.as_mut()?;
+
+        if node.item == *item {
+            let mut node = slot.take().unwrap();
+            let mut nodes = vec![node.inside.take(), node.outside.take()];
+            nodes.retain(Option::is_some);
+            Self::unlink(&mut nodes);
+            *slot = Self::balanced_recursive(&mut nodes);
+            return Some(node.item);
+        }
 
-                if r1 <= r2 {
-                    self.radius = r2.into();
-                    self.inside = Some(node);
-                    self.outside = Some(other);
-                } else {
-                    self.radius = r1.into();
-                    self.inside = Some(other);
-                    self.outside = Some(node);
-                }
+        // Ties with the radius can end up on either side
+        let distance: DistanceValue<T> = node.item.distance(item).into();
+        if distance <= node.radius {
+            if let Some(removed) = Self::remove(&mut node.inside, item) {
+                return Some(removed);
             }
         }
+        if distance >= node.radius {
+            Self::remove(&mut node.outside, item)
+        } else {
+            None
+        }
+    }
+}
+
+/// A node of a [`VpTree`] in its serialized form, which lists the nodes in pre-order.
+#[cfg(feature = "serde")]
+#[derive(Deserialize, Serialize)]
+#[serde(rename = "VpNode")]
+struct VpNodeData<T, R> {
+    /// The vantage point itself.
+    item: T,
+    /// The radius of this node.
+    radius: R,
+    /// Whether this node has a subtree inside the radius.
+    inside: bool,
+    /// Whether this node has a subtree outside the radius.
+    outside: bool,
+}
+
+#[cfg(feature = "serde")]
+impl<T, R> VpNode<T, R> {
+    /// Iterate over the nodes of this subtree in pre-order, without recursion.
+    fn pre_order(&self) -> impl Iterator<Item = VpNodeData<&T, &R>> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.outside.as_deref());
+            stack.extend(node.inside.as_deref());
+            Some(VpNodeData {
+                item: &node.item,
+                radius: &node.radius,
+                inside: node.inside.is_some(),
+                outside: node.outside.is_some(),
+            })
+        })
     }
 }
 
-trait VpSearch<K, V, N>: Copy
+trait VpSearch<K, V>: Copy
 where
     K: Proximity<V, Distance = V::Distance>,
     V: Proximity,
-    N: Neighborhood<K, V>,
 {
     /// Get the vantage point of this node.
     fn item(self) -> V;
@@ -129,42 +225,72 @@ where
     fn outside(self) -> Option<Self>;
 
     /// Recursively search for nearest neighbors.
-    fn search(self, neighborhood: &mut N) {
+    ///
+    /// Subtrees are pruned when `factor` times their distance bound is out of range, so a `factor`
+    /// of one gives exact results.
+    fn search<N>(self, depth: usize, factor: DistanceValue<V>, neighborhood: &mut N)
+    where
+        N: Neighborhood<K, V>,
+    {
+        if neighborhood.is_exhausted() {
+            return;
+        }
+
+        neighborhood.visit(depth);
         let distance = neighborhood.consider(self.item()).into();
 
         if distance <= self.radius() {
-            self.search_inside(distance, neighborhood);
-            self.search_outside(distance, neighborhood);
+            self.search_inside(distance, depth, factor, neighborhood);
+            self.search_outside(distance, depth, factor, neighborhood);
         } else {
-            self.search_outside(distance, neighborhood);
-            self.search_inside(distance, neighborhood);
+            self.search_outside(distance, depth, factor, neighborhood);
+            self.search_inside(distance, depth, factor, neighborhood);
         }
     }
 
     /// Search the inside subtree.
-    fn search_inside(self, distance: DistanceValue<V>, neighborhood: &mut N) {
+    fn search_inside<N>(
+        self,
+        distance: DistanceValue<V>,
+        depth: usize,
+        factor: DistanceValue<V>,
+        neighborhood: &mut N,
+    ) where
+        N: Neighborhood<K, V>,
+    {
         if let Some(inside) = self.inside() {
-            if neighborhood.contains(distance - self.radius()) {
-                inside.search(neighborhood);
+            if neighborhood.contains((distance - self.radius()) * factor) {
+                inside.search(depth + 1, factor, neighborhood);
+            } else {
+                neighborhood.prune(depth + 1);
             }
         }
     }
 
     /// Search the outside subtree.
-    fn search_outside(self, distance: DistanceValue<V>, neighborhood: &mut N) {
+    fn search_outside<N>(
+        self,
+        distance: DistanceValue<V>,
+        depth: usize,
+        factor: DistanceValue<V>,
+        neighborhood: &mut N,
+    ) where
+        N: Neighborhood<K, V>,
+    {
         if let Some(outside) = self.outside() {
-            if neighborhood.contains(self.radius() - distance) {
-                outside.search(neighborhood);
+            if neighborhood.contains((self.radius() - distance) * factor) {
+                outside.search(depth + 1, factor, neighborhood);
+            } else {
+                neighborhood.prune(depth + 1);
             }
         }
     }
 }
 
-impl<'a, K, V, N> VpSearch<K, &'a V, N> for &'a VpNode<V>
+impl<'a, K, V> VpSearch<K, &'a V> for &'a VpNode<V>
 where
     K: Proximity<&'a V, Distance = V::Distance>,
     V: Proximity,
-    N: Neighborhood<K, &'a V>,
 {
     fn item(self) -> &'a V {
         &self.item
@@ -183,48 +309,134 @@ where
     }
 }
 
+/// A best-first traversal of a VP tree, in order of distance from a target.
+struct VpBrowse<'k, 'v, K, V: Proximity, S> {
+    /// The target of the traversal.
+    target: &'k K,
+    /// The subtrees and items left to visit.
+    queue: BinaryHeap<BestFirst<S, &'v V, DistanceValue<V>, V::Distance>>,
+}
+
+impl<'k, 'v, K, V, S> VpBrowse<'k, 'v, K, V, S>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+    S: VpSearch<&'k K, &'v V>,
+{
+    /// Start a traversal from the root of a tree.
+    fn new(target: &'k K, root: Option<S>) -> Self {
+        let mut queue = BinaryHeap::new();
+        if let Some(root) = root {
+            queue.push(BestFirst::Node(root, zero()));
+        }
+
+        Self { target, queue }
+    }
+
+    /// Find the next nearest neighbor.
+    fn next(&mut self) -> Option<Neighbor<&'v V, V::Distance>> {
+        while let Some(entry) = self.queue.pop() {
+            let (node, bound) = match entry {
+                BestFirst::Item(item, distance) => return Some(Neighbor::new(item, distance)),
+                BestFirst::Node(node, bound) => (node, bound),
+            };
+
+            let item = node.item();
+            let distance = self.target.distance(item);
+            self.queue.push(BestFirst::Item(item, distance));
+
+            // Everything inside is at least distance - radius away, and everything outside is at
+            // least radius - distance away
+            let distance: DistanceValue<V> = distance.into();
+            let radius = node.radius();
+            if let Some(inside) = node.inside() {
+                self.push_node(inside, bound, distance - radius);
+            }
+            if let Some(outside) = node.outside() {
+                self.push_node(outside, bound, radius - distance);
+            }
+        }
+
+        None
+    }
+
+    /// Queue a subtree, given its parent's bound and a new bound.
+    fn push_node(&mut self, node: S, parent: DistanceValue<V>, bound: DistanceValue<V>) {
+        let bound = if bound > parent { bound } else { parent };
+        self.queue.push(BestFirst::Node(node, bound));
+    }
+}
+
+impl<'k, 'v, K, V, S> Debug for VpBrowse<'k, 'v, K, V, S>
+where
+    K: Debug,
+    V: Proximity + Debug,
+    S: Debug,
+    V::Distance: Debug,
+    DistanceValue<V>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("VpBrowse")
+            .field("target", &self.target)
+            .field("queue", &self.queue)
+            .finish()
+    }
+}
+
 /// A [vantage-point tree](https://en.wikipedia.org/wiki/Vantage-point_tree).
 pub struct VpTree<T: Proximity> {
     root: Option<VpNode<T>>,
+    len: usize,
 }
 
 impl<T: Proximity> VpTree<T> {
     /// Create an empty tree.
     pub fn new() -> Self {
-        Self { root: None }
+        Self {
+            root: None,
+            len: 0,
+        }
     }
 
     /// Create a balanced tree out of a sequence of items.
     pub fn balanced<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut nodes = VpNode::nodes(items);
+        Self::from_nodes(&mut nodes)
+    }
+
+    /// Create a balanced tree out of some unlinked nodes.
+    fn from_nodes(nodes: &mut [Option<Box<VpNode<T>>>]) -> Self {
         Self {
-            root: VpNode::balanced(items),
+            root: VpNode::balanced_recursive(nodes).map(|node| *node),
+            len: nodes.len(),
         }
     }
 
-    /// Rebalance this VP tree.
-    pub fn balance(&mut self) {
+    /// Take all the nodes out of this tree, unlinked from each other.
+    fn take_nodes(&mut self) -> Vec<Option<Box<VpNode<T>>>> {
         let mut nodes = Vec::new();
         if let Some(root) = self.root.take() {
             nodes.push(Some(Box::new(root)));
         }
+        VpNode::unlink(&mut nodes);
+        self.len = 0;
+        nodes
+    }
 
-        let mut i = 0;
-        while i < nodes.len() {
-            let node = nodes[i].as_mut().unwrap();
-            let inside = node.inside.take();
-            let outside = node.outside.take();
-            if inside.is_some() {
-                nodes.push(inside);
-            }
-            if outside.is_some() {
-                nodes.push(outside);
-            }
+    /// Rebalance this VP tree.
+    pub fn balance(&mut self) {
+        let mut nodes = self.take_nodes();
+        *self = Self::from_nodes(&mut nodes);
+    }
 
-            i += 1;
-        }
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        self.root = VpNode::balanced_recursive(&mut nodes)
-            .map(|node| *node);
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     /// Push a new item into the tree.
@@ -237,6 +449,44 @@ impl<T: Proximity> VpTree<T> {
         } else {
             self.root = Some(VpNode::new(item));
         }
+        self.len += 1;
+    }
+
+    /// Remove an item from the tree, returning it if it was present.
+    ///
+    /// The subtree below the removed item is rebuilt, so removals near the root are expensive.
+    /// Use [VpTree::retain] to remove many items at once.
+    pub fn remove(&mut self, item: &T) -> Option<T>
+    where
+        T: PartialEq,
+    {
+        let mut root = self.root.take().map(Box::new);
+        let removed = VpNode::remove(&mut root, item);
+        self.root = root.map(|node| *node);
+
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Retain only the items that satisfy a predicate, rebalancing the tree.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let mut nodes = self.take_nodes();
+        nodes.retain(|node| f(&node.as_ref().unwrap().item));
+        *self = Self::from_nodes(&mut nodes);
+    }
+
+    /// Iterate over the items in this tree, from nearest to farthest from a target.
+    ///
+    /// The neighbors are found lazily by a best-first search, so this is useful when the number of
+    /// neighbors needed isn't known up front.  The order is only guaranteed to be exact for
+    /// [metric spaces](Metric).
+    pub fn nearest_iter<'k, 'v, K>(&'v self, target: &'k K) -> NearestIter<'k, 'v, K, T>
+    where
+        K: Proximity<T, Distance = T::Distance>,
+    {
+        NearestIter(VpBrowse::new(target, self.root.as_ref()))
     }
 }
 
@@ -249,10 +499,69 @@ where
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct("VpTree")
             .field("root", &self.root)
+            .field("len", &self.len)
             .finish()
     }
 }
 
+/// Serializes the nodes of the tree in pre-order, so that even very deep trees can be serialized
+/// without recursion.
+#[cfg(feature = "serde")]
+impl<T> Serialize for VpTree<T>
+where
+    T: Proximity + Serialize,
+    DistanceValue<T>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        for node in self.root.iter().flat_map(VpNode::pre_order) {
+            seq.serialize_element(&node)?;
+        }
+        seq.end()
+    }
+}
+
+/// Deserializes the tree exactly as it was serialized, after checking that its layout is valid.
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for VpTree<T>
+where
+    T: Proximity + Deserialize<'de>,
+    DistanceValue<T>: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let nodes: Vec<VpNodeData<T, DistanceValue<T>>> = Vec::deserialize(deserializer)?;
+        let len = nodes.len();
+        let invalid = || de::Error::custom("invalid VpTree layout");
+
+        // Build the subtrees from the bottom up, so the inside one is always on top of the stack
+        let mut subtrees = Vec::new();
+        for node in nodes.into_iter().rev() {
+            let inside = if node.inside {
+                Some(subtrees.pop().ok_or_else(invalid)?)
+            } else {
+                None
+            };
+            let outside = if node.outside {
+                Some(subtrees.pop().ok_or_else(invalid)?)
+            } else {
+                None
+            };
+            subtrees.push(Box::new(VpNode {
+                item: node.item,
+                radius: node.radius,
+                inside,
+                outside,
+            }));
+        }
+
+        if subtrees.len() > 1 {
+            return Err(invalid());
+        }
+        let root = subtrees.pop().map(|node| *node);
+        Ok(Self { root, len })
+    }
+}
+
 impl<T: Proximity> Default for VpTree<T> {
     fn default() -> Self {
         Self::new()
@@ -266,7 +575,7 @@ impl<T: Proximity> Extend<T> for VpTree<T> {
                 self.push(item);
             }
         } else {
-            self.root = VpNode::balanced(items);
+            *self = Self::balanced(items);
         }
     }
 }
@@ -339,7 +648,7 @@ where
         N: Neighborhood<&'k K, &'v V>,
     {
         if let Some(root) = &self.root {
-            root.search(&mut neighborhood);
+            root.search(0, one(), &mut neighborhood);
         }
         neighborhood
     }
@@ -351,8 +660,59 @@ where
     V: Metric,
 {}
 
+/// (1 + ε)-approximate searches in a VP tree.
+impl<'a, K, V> NearestNeighbors<K, V> for Approximate<'a, VpTree<V>, DistanceValue<V>>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if let Some(root) = &self.index().root {
+            root.search(0, self.factor(), &mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// An iterator over the items in a VP tree, from nearest to farthest from a target.
+///
+/// Created by [`VpTree::nearest_iter()`].
+pub struct NearestIter<'k, 'v, K, V: Proximity>(VpBrowse<'k, 'v, K, V, &'v VpNode<V>>);
+
+impl<'k, 'v, K, V> Debug for NearestIter<'k, 'v, K, V>
+where
+    K: Debug,
+    V: Proximity + Debug,
+    V::Distance: Debug,
+    DistanceValue<V>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("NearestIter")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<'k, 'v, K, V> Iterator for NearestIter<'k, 'v, K, V>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+{
+    type Item = Neighbor<&'v V, V::Distance>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
 /// A node in a flat VP tree.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 struct FlatVpNode<T, R = DistanceValue<T>> {
     /// The vantage point itself.
     item: T,
@@ -387,26 +747,93 @@ impl<T: Proximity> FlatVpNode<T> {
     /// Create a balanced subtree.
     fn balance_recursive(nodes: &mut [Self]) {
         if let Some((node, children)) = nodes.split_first_mut() {
-            children.sort_by_cached_key(|x| Ordered::new(node.item.distance(&x.item)));
-
-            let (inside, outside) = children.split_at_mut(children.len() / 2);
-            if let Some(last) = inside.last() {
-                node.radius = node.item.distance(&last.item).into();
+            // Stash the distances in the children's radii, to compute them only once
+            for child in children.iter_mut() {
+                child.radius = node.item.distance(&child.item).value();
             }
 
-            node.inside_len = inside.len();
-
+            let (inside, outside) = node.partition(children);
             Self::balance_recursive(inside);
             Self::balance_recursive(outside);
         }
     }
+
+    /// Split the children of this node around the median of their stashed distances.
+    fn partition<'a>(&mut self, children: &'a mut [Self]) -> (&'a mut [Self], &'a mut [Self]) {
+        let mid = children.len() / 2;
+        self.radius = if mid > 0 {
+            children.select_nth_unstable_by_key(mid - 1, |x| Ordered::new(x.radius));
+            children[mid - 1].radius
+        } else {
+            zero()
+        };
+
+        self.inside_len = mid;
+        children.split_at_mut(mid)
+    }
 }
 
-impl<'a, K, V, N> VpSearch<K, &'a V, N> for &'a [FlatVpNode<V>]
+#[cfg(feature = "rayon")]
+impl<T> FlatVpNode<T>
+where
+    T: Proximity + Send + Sync,
+    DistanceValue<T>: Send,
+{
+    /// Create a balanced tree in parallel.
+    fn par_balanced<I: IntoParallelIterator<Item = T>>(items: I) -> Vec<Self> {
+        let mut nodes: Vec<_> = items
+            .into_par_iter()
+            .map(Self::new)
+            .collect();
+
+        Self::par_balance_recursive(&mut nodes);
+
+        nodes
+    }
+
+    /// Create a balanced subtree in parallel.
+    fn par_balance_recursive(nodes: &mut [Self]) {
+        if nodes.len() < PAR_MIN_LEN {
+            return Self::balance_recursive(nodes);
+        }
+
+        let (node, children) = nodes.split_first_mut().unwrap();
+        let item = &node.item;
+        children
+            .par_iter_mut()
+            .for_each(|child| child.radius = item.distance(&child.item).value());
+
+        let (inside, outside) = node.partition(children);
+        rayon::join(
+            || Self::par_balance_recursive(inside),
+            || Self::par_balance_recursive(outside),
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Proximity> FlatVpNode<T> {
+    /// Check that the subtree sizes of a flat tree are consistent.
+    fn is_valid(nodes: &[Self]) -> bool {
+        let mut stack = vec![nodes];
+        while let Some(nodes) = stack.pop() {
+            if let Some((node, children)) = nodes.split_first() {
+                if node.inside_len > children.len() {
+                    return false;
+                }
+                let (inside, outside) = children.split_at(node.inside_len);
+                stack.push(inside);
+                stack.push(outside);
+            }
+        }
+        true
+    }
+}
+
+impl<'a, K, V> VpSearch<K, &'a V> for &'a [FlatVpNode<V>]
 where
     K: Proximity<&'a V, Distance = V::Distance>,
     V: Proximity,
-    N: Neighborhood<K, &'a V>,
 {
     fn item(self) -> &'a V {
         &self[0].item
@@ -452,6 +879,79 @@ impl<T: Proximity> FlatVpTree<T> {
             nodes: FlatVpNode::balanced(items),
         }
     }
+
+    /// Create a balanced tree out of a sequence of items, in parallel.
+    ///
+    /// This builds the same tree as [`balanced()`](Self::balanced), using the current [rayon]
+    /// thread pool.  Only available with the `rayon` feature.
+    ///
+    /// [rayon]: https://docs.rs/rayon
+    #[cfg(feature = "rayon")]
+    pub fn par_balanced<I: IntoParallelIterator<Item = T>>(items: I) -> Self
+    where
+        T: Send + Sync,
+        DistanceValue<T>: Send,
+    {
+        Self {
+            nodes: FlatVpNode::par_balanced(items),
+        }
+    }
+
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Write this tree in the zero-copy [flat format](crate::flat).
+    ///
+    /// Every item must have the same number of coordinates.  The tree is written in many small
+    /// pieces, so `writer` should usually be buffered.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        T: Coordinates,
+        T::Value: Scalar,
+        DistanceValue<T>: Scalar,
+    {
+        let dims = self.nodes.first().map_or(0, |node| node.item.dims());
+        let coords = T::Value::TAG;
+        let radii = DistanceValue::<T>::TAG;
+        let mut writer = Writer::new(writer, Kind::Vp, coords, radii, dims, self.nodes.len())?;
+
+        for node in &self.nodes {
+            writer.write_item(&node.item)?;
+        }
+        writer.end_section()?;
+
+        for node in &self.nodes {
+            writer.write(node.radius)?;
+        }
+        writer.end_section()?;
+
+        for node in &self.nodes {
+            writer.write_len(node.inside_len)?;
+        }
+        writer.end_section()
+    }
+
+    /// Iterate over the items in this tree, from nearest to farthest from a target.
+    ///
+    /// See [`VpTree::nearest_iter()`].
+    pub fn nearest_iter<'k, 'v, K>(&'v self, target: &'k K) -> FlatNearestIter<'k, 'v, K, T>
+    where
+        K: Proximity<T, Distance = T::Distance>,
+    {
+        let root = if self.nodes.is_empty() {
+            None
+        } else {
+            Some(self.nodes.as_slice())
+        };
+        FlatNearestIter(VpBrowse::new(target, root))
+    }
 }
 
 impl<T> Debug for FlatVpTree<T>
@@ -466,6 +966,34 @@ where
     }
 }
 
+#[cfg(feature = "serde")]
+impl<T> Serialize for FlatVpTree<T>
+where
+    T: Proximity + Serialize,
+    DistanceValue<T>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.nodes.serialize(serializer)
+    }
+}
+
+/// Deserializes the tree exactly as it was serialized, after checking that its layout is valid.
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for FlatVpTree<T>
+where
+    T: Proximity + Deserialize<'de>,
+    DistanceValue<T>: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let nodes = Vec::deserialize(deserializer)?;
+        if FlatVpNode::is_valid(&nodes) {
+            Ok(Self { nodes })
+        } else {
+            Err(de::Error::custom("invalid FlatVpTree layout"))
+        }
+    }
+}
+
 impl<T: Proximity> FromIterator<T> for FlatVpTree<T> {
     fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
         Self::balanced(items)
@@ -504,6 +1032,37 @@ impl<T: Proximity> IntoIterator for FlatVpTree<T> {
     }
 }
 
+/// An iterator over the items in a flat VP tree, from nearest to farthest from a target.
+///
+/// Created by [`FlatVpTree::nearest_iter()`].
+pub struct FlatNearestIter<'k, 'v, K, V: Proximity>(VpBrowse<'k, 'v, K, V, &'v [FlatVpNode<V>]>);
+
+impl<'k, 'v, K, V> Debug for FlatNearestIter<'k, 'v, K, V>
+where
+    K: Debug,
+    V: Proximity + Debug,
+    V::Distance: Debug,
+    DistanceValue<V>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("FlatNearestIter")
+            .field(&self.0)
+            .finish()
+    }
+}
+
+impl<'k, 'v, K, V> Iterator for FlatNearestIter<'k, 'v, K, V>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+{
+    type Item = Neighbor<&'v V, V::Distance>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
 impl<K, V> NearestNeighbors<K, V> for FlatVpTree<V>
 where
     K: Proximity<V, Distance = V::Distance>,
@@ -516,7 +1075,7 @@ where
         N: Neighborhood<&'k K, &'v V>,
     {
         if !self.nodes.is_empty() {
-            self.nodes.as_slice().search(&mut neighborhood);
+            self.nodes.as_slice().search(0, one(), &mut neighborhood);
         }
         neighborhood
     }
@@ -528,11 +1087,185 @@ where
     V: Metric,
 {}
 
+/// (1 + ε)-approximate searches in a flat VP tree.
+impl<'a, K, V> NearestNeighbors<K, V> for Approximate<'a, FlatVpTree<V>, DistanceValue<V>>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        let nodes = &self.index().nodes;
+        if !nodes.is_empty() {
+            nodes.as_slice().search(0, self.factor(), &mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+/// The nodes of a flat VP tree in the [flat format](crate::flat).
+struct FlatVpSlices<'a, T: Proximity> {
+    /// The vantage points.
+    items: &'a [T],
+    /// The radii.
+    radii: &'a [DistanceValue<T>],
+    /// The sizes of the inside subtrees.
+    inside_lens: &'a [u64],
+}
+
+impl<'a, T: Proximity> FlatVpSlices<'a, T> {
+    /// Get the nodes in a range.
+    fn slice(self, start: usize, end: usize) -> Self {
+        Self {
+            items: &self.items[start..end],
+            radii: &self.radii[start..end],
+            inside_lens: &self.inside_lens[start..end],
+        }
+    }
+}
+
+impl<'a, T: Proximity> Clone for FlatVpSlices<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: Proximity> Copy for FlatVpSlices<'a, T> {}
+
+impl<'a, K, V> VpSearch<K, &'a V> for FlatVpSlices<'a, V>
+where
+    K: Proximity<&'a V, Distance = V::Distance>,
+    V: Proximity,
+{
+    fn item(self) -> &'a V {
+        &self.items[0]
+    }
+
+    fn radius(self) -> DistanceValue<V> {
+        self.radii[0]
+    }
+
+    fn inside(self) -> Option<Self> {
+        let end = (self.inside_lens[0] as usize).saturating_add(1);
+        if end > 1 {
+            Some(self.slice(1, end))
+        } else {
+            None
+        }
+    }
+
+    fn outside(self) -> Option<Self> {
+        let start = (self.inside_lens[0] as usize).saturating_add(1);
+        if start < self.items.len() {
+            Some(self.slice(start, self.items.len()))
+        } else {
+            None
+        }
+    }
+}
+
+/// A [`FlatVpTree`] borrowed from a byte buffer in the zero-copy [flat format](crate::flat).
+///
+/// The items are read in place, so they must be [plain old data](Pod).  A tree written from items
+/// with a dynamic number of coordinates, like `Euclidean<Vec<f32>>`, can be read back with an
+/// array type of the same width, like `Euclidean<[f32; 128]>`.
+pub struct FlatVpTreeRef<'a, T: Proximity> {
+    nodes: FlatVpSlices<'a, T>,
+}
+
+impl<'a, T> FlatVpTreeRef<'a, T>
+where
+    T: Pod + Proximity,
+    DistanceValue<T>: Scalar,
+{
+    /// Open a tree stored in a byte buffer.
+    ///
+    /// Only the header is checked, so this takes constant time.  The buffer must be aligned for
+    /// the item and radius types.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        let mut reader = Reader::new::<T>(bytes, Kind::Vp, DistanceValue::<T>::TAG)?;
+        let items = reader.section()?;
+        let radii = reader.section()?;
+        let inside_lens = reader.section()?;
+
+        Ok(Self {
+            nodes: FlatVpSlices {
+                items,
+                radii,
+                inside_lens,
+            },
+        })
+    }
+}
+
+impl<'a, T: Proximity> FlatVpTreeRef<'a, T> {
+    /// Get the size of this tree.
+    pub fn len(&self) -> usize {
+        self.nodes.items.len()
+    }
+
+    /// Check if this tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.items.is_empty()
+    }
+}
+
+impl<'a, T> Debug for FlatVpTreeRef<'a, T>
+where
+    T: Proximity + Debug,
+    DistanceValue<T>: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("FlatVpTreeRef")
+            .field("items", &self.nodes.items)
+            .field("radii", &self.nodes.radii)
+            .field("inside_lens", &self.nodes.inside_lens)
+            .finish()
+    }
+}
+
+impl<'a, K, V> NearestNeighbors<K, V> for FlatVpTreeRef<'a, V>
+where
+    K: Proximity<V, Distance = V::Distance>,
+    V: Proximity,
+{
+    fn search<'k, 'v, N>(&'v self, mut neighborhood: N) -> N
+    where
+        K: 'k,
+        V: 'v,
+        N: Neighborhood<&'k K, &'v V>,
+    {
+        if !self.is_empty() {
+            let nodes: FlatVpSlices<'v, V> = self.nodes;
+            nodes.search(0, one(), &mut neighborhood);
+        }
+        neighborhood
+    }
+}
+
+impl<'a, K, V> ExactNeighbors<K, V> for FlatVpTreeRef<'a, V>
+where
+    K: Metric<V, Distance = V::Distance>,
+    V: Metric,
+{}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::tests::test_exact_neighbors;
+    use crate::euclid::Euclidean;
+    use crate::exhaustive::ExhaustiveSearch;
+    use crate::flat::tests::aligned;
+    use crate::tests::{test_exact_neighbors, test_remove, test_retain};
+    use crate::{SingletonNeighborhood, StatsNeighborhood};
+
+    use rand::prelude::*;
+
+    use std::fmt::Debug;
 
     #[test]
     fn test_vp_tree() {
@@ -550,8 +1283,256 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_vp_tree_remove() {
+        test_remove(VpTree::from_iter, VpTree::remove, VpTree::len);
+    }
+
+    /// Check that every item in a subtree is on the correct side of its ancestors' radii.
+    fn assert_valid<T: Proximity + Debug>(node: &VpNode<T>) -> Vec<&T> {
+        let inside = node.inside.as_deref().map_or_else(Vec::new, assert_valid);
+        for item in &inside {
+            assert!(node.item.distance(item).value() <= node.radius, "{:?}", item);
+        }
+
+        let outside = node.outside.as_deref().map_or_else(Vec::new, assert_valid);
+        for item in &outside {
+            assert!(node.item.distance(item).value() >= node.radius, "{:?}", item);
+        }
+
+        let mut items = inside;
+        items.extend(outside);
+        items.push(&node.item);
+        items
+    }
+
+    #[test]
+    fn test_vp_tree_push_after_remove() {
+        let random_point = || Euclidean([random::<f32>(), random()]);
+
+        for _ in 0..100 {
+            let points: Vec<_> = (0..40).map(|_| random_point()).collect();
+            let mut tree = VpTree::balanced(points.clone());
+            let mut exhaustive = ExhaustiveSearch::from_iter(points[30..].iter().copied());
+            for point in &points[..30] {
+                assert_eq!(tree.remove(point).as_ref(), Some(point));
+            }
+
+            // Removals can leave nodes with a single child that has its own descendants
+            for _ in 0..30 {
+                let point = random_point();
+                tree.push(point);
+                exhaustive.push(point);
+            }
+            assert_valid(tree.root.as_ref().unwrap());
+
+            for _ in 0..10 {
+                let target = random_point();
+                assert_eq!(tree.k_nearest(&target, 5), exhaustive.k_nearest(&target, 5));
+            }
+        }
+    }
+
+    #[test]
+    fn test_vp_tree_remove_root() {
+        let points: Vec<_> = (0..256)
+            .map(|_| Euclidean([random::<f32>(), random(), random()]))
+            .collect();
+        let mut tree = VpTree::from_iter(points.clone());
+        let mut exhaustive = ExhaustiveSearch::from_iter(points);
+
+        for _ in 0..32 {
+            // Remove the root, and one of its children, which has children of its own at first
+            let root = tree.root.as_ref().unwrap();
+            let child = root.inside.as_ref().or(root.outside.as_ref()).unwrap();
+            for item in &[root.item, child.item] {
+                assert_eq!(tree.remove(item).as_ref(), Some(item));
+                assert_eq!(exhaustive.remove(item).as_ref(), Some(item));
+            }
+
+            let target = Euclidean([random(), random(), random()]);
+            assert_eq!(tree.k_nearest(&target, 3), exhaustive.k_nearest(&target, 3));
+        }
+        assert_eq!(tree.len(), 192);
+    }
+
+    #[test]
+    fn test_vp_tree_retain() {
+        let from_iter = |points| {
+            let mut tree = VpTree::new();
+            for point in points {
+                tree.push(point);
+            }
+            tree
+        };
+        test_retain(from_iter, |tree, f| tree.retain(f), VpTree::len);
+    }
+
     #[test]
     fn test_flat_vp_tree() {
         test_exact_neighbors(FlatVpTree::from_iter);
     }
+
+    #[test]
+    fn test_flat_vp_tree_ref() {
+        test_exact_neighbors(|points| {
+            let mut bytes = Vec::new();
+            FlatVpTree::from_iter(points).write_to(&mut bytes).unwrap();
+            FlatVpTreeRef::<Euclidean<[f32; 3]>>::from_bytes(aligned(&bytes)).unwrap()
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_par_flat_vp_tree() {
+        test_exact_neighbors(FlatVpTree::par_balanced);
+
+        let points: Vec<Euclidean<[f32; 3]>> = (0..(4 * PAR_MIN_LEN))
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let tree = FlatVpTree::balanced(points.clone());
+        let par_tree = FlatVpTree::par_balanced(points);
+        assert!(tree.into_iter().eq(par_tree));
+    }
+
+    #[test]
+    fn test_nearest_iter() {
+        let points: Vec<Euclidean<[f32; 3]>> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let target = Euclidean([random(), random(), random()]);
+
+        let eindex = ExhaustiveSearch::from_iter(points.clone());
+        let expected = eindex.k_nearest(&target, points.len());
+
+        let empty = VpTree::<Euclidean<[f32; 3]>>::new();
+        assert!(empty.nearest_iter(&target).next().is_none());
+
+        let tree = VpTree::from_iter(points.clone());
+        let neighbors: Vec<_> = tree.nearest_iter(&target).collect();
+        assert_eq!(neighbors, expected);
+
+        let mut tree = VpTree::new();
+        tree.extend(points.iter().copied());
+        for point in &points {
+            tree.push(*point);
+        }
+        let neighbors: Vec<_> = tree.nearest_iter(&target).step_by(2).collect();
+        assert_eq!(neighbors, expected);
+
+        let flat = FlatVpTree::from_iter(points.clone());
+        let neighbors: Vec<_> = flat.nearest_iter(&target).take(10).collect();
+        assert_eq!(neighbors, &expected[..10]);
+    }
+
+    #[test]
+    fn test_approximate() {
+        let points: Vec<Euclidean<[f32; 3]>> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let eindex = ExhaustiveSearch::from_iter(points.clone());
+        let tree = VpTree::from_iter(points.clone());
+        let flat = FlatVpTree::from_iter(points.clone());
+
+        for _ in 0..16 {
+            let target = Euclidean([random(), random(), random()]);
+            let expected = eindex.k_nearest(&target, 3);
+
+            assert_eq!(Approximate::new(&tree, 0.0).k_nearest(&target, 3), expected);
+            assert_eq!(Approximate::new(&flat, 0.0).k_nearest(&target, 3), expected);
+
+            let approx = Approximate::new(&tree, 1.0);
+            let neighbors = approx.k_nearest(&target, 3);
+            let flat_approx = Approximate::new(&flat, 1.0);
+            let flat_neighbors = flat_approx.k_nearest(&target, 3);
+            assert_eq!(neighbors.len(), 3);
+            assert_eq!(flat_neighbors.len(), 3);
+            for ((n, f), e) in neighbors.iter().zip(&flat_neighbors).zip(&expected) {
+                assert!(n.distance <= 2.0 * e.distance.value());
+                assert!(f.distance <= 2.0 * e.distance.value());
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let points: Vec<Euclidean<[f32; 3]>> = (0..256)
+            .map(|_| Euclidean([random(), random(), random()]))
+            .collect();
+        let tree = VpTree::from_iter(points.clone());
+        let flat = FlatVpTree::from_iter(points.clone());
+        let target = Euclidean([random(), random(), random()]);
+
+        let singleton = SingletonNeighborhood::new(&target, None);
+        let stats = tree.search(StatsNeighborhood::new(singleton)).stats();
+        assert_eq!(stats.distance_evals, stats.nodes_visited);
+        assert!(stats.nodes_visited < points.len());
+        assert!(stats.subtrees_pruned > 0);
+        assert!(stats.max_depth <= 8);
+
+        let singleton = SingletonNeighborhood::new(&target, None);
+        let flat_stats = flat.search(StatsNeighborhood::new(singleton)).stats();
+        assert_eq!(flat_stats, stats);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_vp_tree_serde() {
+        test_exact_neighbors(|points| {
+            let index = VpTree::from_iter(points);
+            let json = serde_json::to_string(&index).unwrap();
+            let copy: VpTree<_> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+            copy
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_deep_vp_tree_serde() {
+        let mut tree = VpTree::new();
+        for i in 0..5000 {
+            tree.push(Euclidean([i as f32, 0.0, 0.0]));
+        }
+
+        let json = serde_json::to_string(&tree).unwrap();
+        let copy: VpTree<Euclidean<[f32; 3]>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+        assert_eq!(copy.len(), 5000);
+
+        let target = Euclidean([2500.2, 0.0, 0.0]);
+        assert_eq!(copy.nearest(&target).unwrap().item, &Euclidean([2500.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_vp_tree_invalid() {
+        let json = r#"[{"item":[0.0,0.0,0.0],"radius":0.0,"inside":true,"outside":false}]"#;
+        assert!(serde_json::from_str::<VpTree<Euclidean<[f32; 3]>>>(json).is_err());
+
+        let json = r#"[
+            {"item":[0.0,0.0,0.0],"radius":0.0,"inside":false,"outside":false},
+            {"item":[1.0,0.0,0.0],"radius":0.0,"inside":false,"outside":false}
+        ]"#;
+        assert!(serde_json::from_str::<VpTree<Euclidean<[f32; 3]>>>(json).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_flat_vp_tree_serde() {
+        test_exact_neighbors(|points| {
+            let index = FlatVpTree::from_iter(points);
+            let json = serde_json::to_string(&index).unwrap();
+            let copy: FlatVpTree<_> = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&copy).unwrap(), json);
+            copy
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_flat_vp_tree_invalid() {
+        let json = r#"[{"item":[0.0,0.0,0.0],"radius":0.0,"inside_len":1}]"#;
+        assert!(serde_json::from_str::<FlatVpTree<Euclidean<[f32; 3]>>>(json).is_err());
+    }
 }